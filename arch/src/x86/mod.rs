@@ -1,5 +1,7 @@
 mod addressing;
+mod msr;
 mod port;
 
 pub use addressing::*;
+pub use msr::*;
 pub use port::*;
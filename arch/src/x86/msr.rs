@@ -0,0 +1,30 @@
+/// Model specific registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Msr(pub u32);
+
+impl Msr {
+    /// Base of the fs segment. On x86_64, this is the thread pointer of the
+    /// thread-local storage.
+    pub const FS_BASE: Msr = Msr(0xc000_0100);
+    /// Base of the gs segment.
+    pub const GS_BASE: Msr = Msr(0xc000_0101);
+    /// The value that swapgs exchanges with GS_BASE.
+    pub const KERNEL_GS_BASE: Msr = Msr(0xc000_0102);
+
+    #[inline(always)]
+    pub unsafe fn read(self) -> u64 {
+        let (lo, hi): (u32, u32);
+        asm!("rdmsr" : "={eax}" (lo), "={edx}" (hi)
+                     : "{ecx}" (self.0) :
+                     : "volatile");
+        (hi as u64) << 32 | lo as u64
+    }
+
+    #[inline(always)]
+    pub unsafe fn write(self, v: u64) {
+        asm!("wrmsr" :
+                     : "{ecx}" (self.0), "{eax}" (v as u32),
+                       "{edx}" ((v >> 32) as u32) :
+                     : "volatile");
+    }
+}
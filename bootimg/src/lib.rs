@@ -0,0 +1,312 @@
+//! The layout of the disk image.
+//!
+//! The bootloader is followed by the image header, which starts at the next
+//! sector. The header describes the payloads, such as the kernel, the
+//! modules and the command line, each of which starts at a sector.
+//!
+//! Each payload has its SHA-256 digest in the header, and the header may be
+//! signed with Ed25519, so that the signature covers the payloads too.
+#![cfg_attr(not(test), no_std)]
+
+use core::mem::{offset_of, size_of};
+use core::{slice, str};
+
+/// The first field of the header.
+pub const IMAGE_MAGIC: u64 = 0x6567616d_69534f72; // "rOSimage"
+/// Bumped on every incompatible change of the layout.
+pub const IMAGE_VERSION: u32 = 2;
+pub const SECTOR_SIZE: u64 = 512;
+
+pub const ENTRIES_MAX: usize = 10;
+pub const NAME_MAX: usize = 32;
+pub const DIGEST_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Header.flags: the header carries a signature.
+pub const IMAGE_SIGNED: u32 = 1 << 0;
+
+// Kinds of the payloads.
+pub const ENTRY_KERNEL: u32 = 1;
+pub const ENTRY_MODULE: u32 = 2;
+pub const ENTRY_CMDLINE: u32 = 3;
+
+/// A payload in the image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Entry {
+    /// One of the ENTRY_* kinds.
+    pub kind: u32,
+    /// The first sector of the payload in the image.
+    pub sector: u32,
+    pub size: u32,
+    pub _reserved: u32,
+    /// SHA-256 of the payload.
+    pub sha256: [u8; DIGEST_SIZE],
+    /// The NUL-terminated name of the payload.
+    pub name: [u8; NAME_MAX],
+}
+
+impl Entry {
+    /// Byte offset of the payload in the image.
+    pub const fn offset(&self) -> u64 {
+        self.sector as u64 * SECTOR_SIZE
+    }
+
+    /// The sector past the payload.
+    pub const fn end(&self) -> u64 {
+        self.sector as u64 + (self.size as u64).div_ceil(SECTOR_SIZE)
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(NAME_MAX);
+        str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+
+/// The video mode that the bootloader sets, or all zeros to keep the text
+/// mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct VideoMode {
+    pub width: u16,
+    pub height: u16,
+    /// Bits per pixel.
+    pub bpp: u8,
+    pub _reserved: [u8; 3],
+}
+
+/// The image header, which takes two sectors.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Header {
+    pub magic: u64,
+    /// IMAGE_VERSION of the builder.
+    pub version: u32,
+    /// Size of this structure.
+    pub size: u32,
+    pub count: u32,
+    /// CRC32 of this structure with `crc32` zeroed.
+    pub crc32: u32,
+    /// IMAGE_* flags.
+    pub flags: u32,
+    pub _reserved0: u32,
+    /// Ed25519 signature of `message()`, if IMAGE_SIGNED.
+    pub signature: [u8; SIGNATURE_SIZE],
+    pub entries: [Entry; ENTRIES_MAX],
+    /// boot.s reads this at the offset 896 in the real mode.
+    pub video: VideoMode,
+    pub _reserved: [u64; 15],
+}
+
+// The header takes two sectors, and boot.s knows where `video` is.
+const _: () = assert!(size_of::<Header>() == 2 * SECTOR_SIZE as usize);
+const _: () = assert!(offset_of!(Header, video) == 896);
+
+const EMPTY: Entry = Entry {
+    kind: 0,
+    sector: 0,
+    size: 0,
+    _reserved: 0,
+    sha256: [0; DIGEST_SIZE],
+    name: [0; NAME_MAX],
+};
+
+impl Header {
+    pub const fn new() -> Self {
+        Header {
+            magic: IMAGE_MAGIC,
+            version: IMAGE_VERSION,
+            size: size_of::<Header>() as u32,
+            count: 0,
+            crc32: 0,
+            flags: 0,
+            _reserved0: 0,
+            signature: [0; SIGNATURE_SIZE],
+            entries: [EMPTY; ENTRIES_MAX],
+            video: VideoMode {
+                width: 0,
+                height: 0,
+                bpp: 0,
+                _reserved: [0; 3],
+            },
+            _reserved: [0; 15],
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(
+                self as *const _ as *const u8,
+                size_of::<Header>(),
+            )
+        }
+    }
+
+    fn checksum(&self) -> u32 {
+        let mut header = *self;
+        header.crc32 = 0;
+        crc32(0, header.as_bytes())
+    }
+
+    /// What the signature signs: this structure with `crc32` and
+    /// `signature` zeroed.
+    pub fn message(&self) -> Header {
+        let mut header = *self;
+        header.crc32 = 0;
+        header.signature = [0; SIGNATURE_SIZE];
+        header
+    }
+
+    /// Fill in `crc32` after the entries and the signature are added.
+    pub fn seal(&mut self) {
+        self.crc32 = self.checksum();
+    }
+
+    /// Check that this was written by a builder of the same version, is
+    /// intact, and has no two payloads that share a sector.
+    pub fn is_valid(&self) -> bool {
+        self.magic == IMAGE_MAGIC
+            && self.version == IMAGE_VERSION
+            && self.size as usize == size_of::<Header>()
+            && self.count as usize <= ENTRIES_MAX
+            && self.crc32 == self.checksum()
+            && !self.overlaps()
+    }
+
+    fn overlaps(&self) -> bool {
+        let entries = self.entries();
+        entries.iter().enumerate().any(|(i, a)| {
+            entries[i + 1..].iter().any(|b| {
+                (a.sector as u64) < b.end() && (b.sector as u64) < a.end()
+            })
+        })
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries[..(self.count as usize).min(ENTRIES_MAX)]
+    }
+
+    /// The first entry of `kind`.
+    pub fn find(&self, kind: u32) -> Option<&Entry> {
+        self.entries().iter().find(|entry| entry.kind == kind)
+    }
+
+    /// Add an entry, unless the header is full.
+    pub fn push(&mut self, entry: Entry) -> Result<(), Entry> {
+        match self.entries.get_mut(self.count as usize) {
+            Some(slot) => {
+                *slot = entry;
+                self.count += 1;
+                Ok(())
+            }
+            None => Err(entry),
+        }
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Update `crc` with `data`, as the IEEE 802.3 CRC32 does.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: u32, sector: u32, size: u32) -> Entry {
+        Entry {
+            kind,
+            sector,
+            size,
+            ..Entry::default()
+        }
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(0, b""), 0);
+        assert_eq!(crc32(0, b"123456789"), 0xcbf43926);
+        // It can be fed in pieces.
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn push_and_find() {
+        let mut header = Header::new();
+        let kernel = entry(ENTRY_KERNEL, 3, 0x1000);
+        let cmdline = entry(ENTRY_CMDLINE, 11, 12);
+        header.push(kernel).unwrap();
+        header.push(cmdline).unwrap();
+        header.seal();
+        assert!(header.is_valid());
+        assert_eq!(header.entries(), &[kernel, cmdline]);
+        assert_eq!(header.find(ENTRY_KERNEL), Some(&kernel));
+        assert_eq!(header.find(ENTRY_CMDLINE), Some(&cmdline));
+        assert_eq!(header.find(ENTRY_MODULE), None);
+        assert_eq!(kernel.offset(), 3 * SECTOR_SIZE);
+
+        for i in 2..ENTRIES_MAX {
+            header.push(entry(ENTRY_MODULE, 12 + i as u32, 1)).unwrap();
+        }
+        let extra = entry(ENTRY_MODULE, 100, 1);
+        assert_eq!(header.push(extra), Err(extra));
+
+        // Any change after the seal shows.
+        header.seal();
+        assert!(header.is_valid());
+        header.entries[0].size += 1;
+        assert!(!header.is_valid());
+    }
+
+    #[test]
+    fn overlapping_entries() {
+        let sealed = |entries: &[Entry]| {
+            let mut header = Header::new();
+            for &e in entries {
+                header.push(e).unwrap();
+            }
+            header.seal();
+            header
+        };
+        // The kernel ends in sector 4, partly.
+        let kernel = entry(ENTRY_KERNEL, 3, SECTOR_SIZE as u32 + 1);
+        assert!(sealed(&[kernel, entry(ENTRY_MODULE, 5, 1)]).is_valid());
+        assert!(!sealed(&[kernel, entry(ENTRY_MODULE, 4, 1)]).is_valid());
+        assert!(!sealed(&[entry(ENTRY_MODULE, 2, 0x400), kernel]).is_valid());
+        // Empty payloads take no sector.
+        assert!(sealed(&[kernel, entry(ENTRY_CMDLINE, 3, 0)]).is_valid());
+    }
+
+    #[test]
+    fn entries_at_the_32_bit_limits() {
+        let last = entry(ENTRY_MODULE, u32::MAX, u32::MAX);
+        assert_eq!(last.offset(), u32::MAX as u64 * SECTOR_SIZE);
+        assert_eq!(last.end(), u32::MAX as u64 + 0x80_0000);
+        let mut header = Header::new();
+        header.push(entry(ENTRY_KERNEL, u32::MAX - 1, 1)).unwrap();
+        header.push(last).unwrap();
+        header.seal();
+        assert!(header.is_valid());
+    }
+
+    #[test]
+    fn layout() {
+        let mut header = Header::new();
+        header.video.width = 0x1234;
+        assert_eq!(header.as_bytes().len(), 1024);
+        assert_eq!(&header.as_bytes()[896..898], &[0x34, 0x12]);
+    }
+}
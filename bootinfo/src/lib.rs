@@ -0,0 +1,251 @@
+//! The boot information that the bootloader hands over to the kernel.
+//!
+//! The bootloader jumps to the kernel entry with `BOOTINFO_MAGIC` in rdi
+//! and the physical address of a `BootInfo` in rsi. Every address in the
+//! boot information is physical.
+#![cfg_attr(not(test), no_std)]
+
+use core::mem::size_of;
+use core::{slice, str};
+
+/// Passed in rdi by the loaders that hand over a `BootInfo`.
+pub const BOOTINFO_MAGIC: u64 = 0x6f666e69_534f5200; // "\0ROSinfo"
+/// Bumped on every incompatible change of `BootInfo`.
+pub const BOOTINFO_VERSION: u32 = 5;
+
+/// A range of the physical memory, from `start` up to `end`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct PhysRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl PhysRange {
+    pub const fn new(start: u64, end: u64) -> Self {
+        PhysRange { start, end }
+    }
+
+    pub const fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+// Types of the memory map entries, as the e820 BIOS call reports them.
+pub const MEMORY_USABLE: u32 = 1;
+pub const MEMORY_RESERVED: u32 = 2;
+pub const MEMORY_ACPI_RECLAIMABLE: u32 = 3;
+pub const MEMORY_ACPI_NVS: u32 = 4;
+pub const MEMORY_BAD: u32 = 5;
+
+// How the loader got the memory map.
+pub const MMAP_SOURCE_NONE: u32 = 0;
+pub const MMAP_SOURCE_E820: u32 = 1;
+/// e801, which only reports the usable memory below and above 1 MiB.
+pub const MMAP_SOURCE_E801: u32 = 2;
+/// int 0x12 and int 0x15 ah=0x88, which see up to 64 MiB.
+pub const MMAP_SOURCE_LEGACY: u32 = 3;
+pub const MMAP_SOURCE_MULTIBOOT2: u32 = 4;
+
+/// An entry of the memory map.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct MemoryEntry {
+    pub addr: u64,
+    pub len: u64,
+    /// One of the MEMORY_* types.
+    pub kind: u32,
+    pub _reserved: u32,
+}
+
+/// A module, such as an initrd, that the bootloader loaded for the kernel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Module {
+    pub range: PhysRange,
+    /// Address of the NUL-terminated command line of the module, or 0.
+    pub cmdline: u64,
+    pub _reserved: u64,
+}
+
+impl Module {
+    /// The command line of the module, if any. The physical memory must be
+    /// mapped at `phys_base`.
+    ///
+    /// # Safety
+    /// `self.cmdline` must be 0 or point to a NUL-terminated string.
+    pub unsafe fn cmdline(&self, phys_base: u64) -> Option<&str> {
+        if self.cmdline == 0 {
+            return None;
+        }
+        let s = (phys_base + self.cmdline) as *const u8;
+        let mut len = 0;
+        while *s.add(len) != 0 {
+            len += 1;
+        }
+        str::from_utf8(slice::from_raw_parts(s, len))
+            .ok()
+            .filter(|s| !s.is_empty())
+    }
+}
+
+/// A linear framebuffer of direct color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Framebuffer {
+    /// The physical address of the framebuffer, or 0 if there is none.
+    pub addr: u64,
+    /// Bytes per line.
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Bits per pixel.
+    pub bpp: u8,
+    // The positions and the sizes of the color fields in a pixel.
+    pub red_pos: u8,
+    pub red_size: u8,
+    pub green_pos: u8,
+    pub green_size: u8,
+    pub blue_pos: u8,
+    pub blue_size: u8,
+    pub _reserved: u8,
+}
+
+/// The boot information.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct BootInfo {
+    /// BOOTINFO_VERSION of the bootloader.
+    pub version: u32,
+    /// Size of this structure.
+    pub size: u32,
+    /// The memory map.
+    pub mmap_addr: u64,
+    pub mmap_count: u32,
+    /// The BIOS drive number that the kernel was loaded from, or
+    /// NO_BOOT_DRIVE.
+    pub boot_drive: u32,
+    /// Where the loadable segments of the kernel are.
+    pub kernel: PhysRange,
+    /// How far the kernel was loaded from its linked addresses, both
+    /// physical and virtual.
+    pub kernel_slide: u64,
+    /// The memory that the bootloader itself occupies, including this
+    /// structure.
+    pub bootloader: PhysRange,
+    /// The memory that the bootloader staged the front of the kernel image
+    /// and its symbols in.
+    pub staging: PhysRange,
+    /// The page tables that the loader left in cr3, or empty if they are
+    /// within the kernel.
+    pub page_tables: PhysRange,
+    /// The kernel command line, without a terminating NUL.
+    pub cmdline_addr: u64,
+    pub cmdline_len: u32,
+    pub mods_count: u32,
+    pub mods_addr: u64,
+    /// The section header table of the kernel, whose non-loadable symbol
+    /// and string tables have their sh_addr set to where they were staged.
+    /// `shdr_num` is 0 if the table was not loaded.
+    pub shdr_addr: u64,
+    pub shdr_num: u32,
+    pub shdr_size: u32,
+    pub shdr_shndx: u32,
+    /// One of the MMAP_SOURCE_* methods.
+    pub mmap_source: u32,
+    pub framebuffer: Framebuffer,
+}
+
+pub const NO_BOOT_DRIVE: u32 = !0;
+
+impl BootInfo {
+    /// Boot information without anything to report.
+    pub const fn new() -> Self {
+        BootInfo {
+            version: BOOTINFO_VERSION,
+            size: size_of::<BootInfo>() as u32,
+            mmap_addr: 0,
+            mmap_count: 0,
+            boot_drive: NO_BOOT_DRIVE,
+            kernel: PhysRange::new(0, 0),
+            kernel_slide: 0,
+            bootloader: PhysRange::new(0, 0),
+            staging: PhysRange::new(0, 0),
+            page_tables: PhysRange::new(0, 0),
+            cmdline_addr: 0,
+            cmdline_len: 0,
+            mods_count: 0,
+            mods_addr: 0,
+            shdr_addr: 0,
+            shdr_num: 0,
+            shdr_size: 0,
+            shdr_shndx: 0,
+            mmap_source: MMAP_SOURCE_NONE,
+            framebuffer: Framebuffer {
+                addr: 0,
+                pitch: 0,
+                width: 0,
+                height: 0,
+                bpp: 0,
+                red_pos: 0,
+                red_size: 0,
+                green_pos: 0,
+                green_size: 0,
+                blue_pos: 0,
+                blue_size: 0,
+                _reserved: 0,
+            },
+        }
+    }
+
+    /// Check that this was written by a bootloader of the same version.
+    pub fn is_valid(&self) -> bool {
+        self.version == BOOTINFO_VERSION
+            && self.size as usize == size_of::<BootInfo>()
+    }
+
+    /// The memory map. The physical memory must be mapped at `phys_base`.
+    ///
+    /// # Safety
+    /// The addresses in `self` must be valid.
+    pub unsafe fn memory_map(&self, phys_base: u64) -> &[MemoryEntry] {
+        table(phys_base, self.mmap_addr, self.mmap_count)
+    }
+
+    /// The modules. The physical memory must be mapped at `phys_base`.
+    ///
+    /// # Safety
+    /// The addresses in `self` must be valid.
+    pub unsafe fn modules(&self, phys_base: u64) -> &[Module] {
+        table(phys_base, self.mods_addr, self.mods_count)
+    }
+
+    /// The kernel command line, if any. The physical memory must be mapped
+    /// at `phys_base`.
+    ///
+    /// # Safety
+    /// The addresses in `self` must be valid.
+    pub unsafe fn cmdline(&self, phys_base: u64) -> Option<&str> {
+        let bytes: &[u8] =
+            table(phys_base, self.cmdline_addr, self.cmdline_len);
+        str::from_utf8(bytes).ok().filter(|s| !s.is_empty())
+    }
+}
+
+impl Default for BootInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The table of `count` entries at the physical address `addr`.
+unsafe fn table<'a, T>(phys_base: u64, addr: u64, count: u32) -> &'a [T] {
+    if addr == 0 || count == 0 {
+        return &[];
+    }
+    slice::from_raw_parts((phys_base + addr) as *const T, count as usize)
+}
@@ -3,6 +3,9 @@
 .intel_syntax noprefix
 .code16
 
+# size_of::<bootimg::Header>() / 512
+.set HEADER_SECTORS, 2
+
 _start:
   cli
   cld
@@ -15,6 +18,10 @@ _start:
 
   mov sp, 0x7000
 
+# The BIOS passes the boot drive in dl.
+  mov dword ptr [0x7000], 0x2       # boot_dev
+  mov byte ptr [0x7000 + 15], dl
+
 # ENABLE A20
 seta20_1:
   in al, 0x64
@@ -32,7 +39,9 @@ seta20_2:
   mov al, 0xdf
   out 0x60, al
 
-# Get a820 map from bios
+# Get a820 map from bios, of at most E820_MAX entries of 24 bytes, which
+# end below VBE_INFO, as MMAP_MAX of info.rs.
+.set E820_MAX, 64
 get_e820:
   mov eax, 0xe820
   mov edi, 0x7000 + 52 + 4        # E820_map + 4
@@ -49,6 +58,8 @@ get_e820:
 
 parse_entry:
   mov [edi - 4], ecx
+  cmp ebp, E820_MAX * 24
+  jae done
   add edi, 24
   mov eax, 0xe820
   mov ecx, 24
@@ -60,12 +71,30 @@ parse_entry:
 
 done:
   mov [edi - 4], ecx
-  mov dword ptr [0x7000], 0x40
+  or dword ptr [0x7000], 0x40       # mmap
   mov dword ptr [0x7000 + 44], ebp
   mov dword ptr [0x7000 + 48], 0x7000 + 52  # E820_map
 fail:
 
 
+# Load the rest of the bootloader and the image header that follows it,
+# a sector at a time, while the BIOS is still there for vbe_setup.
+  mov si, offset dap
+load_boot_loader:
+  mov dl, byte ptr [0x7000 + 15]
+  mov ah, 0x42
+  int 0x13
+  jc boot_fail
+  add word ptr [dap + 6], 0x20      # segment += 512 / 16
+  inc dword ptr [dap + 8]           # lba++
+  cmp dword ptr [dap + 8], offset boot_sectors + HEADER_SECTORS
+  jb load_boot_loader
+
+  # Not a call, as LLVM makes it a 32-bit one that the 16-bit ret can't
+  # return from. mem_setup goes on to vbe_setup, which comes back here.
+  jmp mem_setup
+vbe_return:
+
 # Switch to protected mode
   lgdt gdt_desc
   mov eax, cr0
@@ -78,35 +107,6 @@ fail:
   push eax
   retf
 
-.code32
-_code32:
-  mov ax, 0x10         # PROT_DS
-  mov ds, ax
-  mov es, ax
-  mov fs, ax
-  mov gs, ax
-  mov ss, ax
-
-
-  # Load the remaining boot loaders
-  mov edi, 0x7c00 # addr
-  xor ecx, ecx    # sector
-load_boot_loader:
-  inc ecx
-  add edi, 0x200
-  lea esi, [boot_end]
-  cmp edi, esi
-  jae end
-  push ecx
-  push edi
-  call read_sector
-  pop edi
-  pop ecx
-  jmp load_boot_loader
-end:
-  jmp _head64
-  hlt
-
 boot_fail:
   mov ax, 0x8A00
   mov dx, 0x8A00
@@ -117,53 +117,23 @@ boot_fail:
 spin:
   jmp spin
 
-read_sector: # edi: dst, ecx: offset
-  call wait_disk
-
-  mov al, 1
-  mov edx, 0x1F2
-  out dx, al
-
-  mov eax, ecx
-  mov edx, 0x1F3
-  out dx, al
-
-  mov eax, ecx
-  shr eax, 0x8
-  mov edx, 0x1F4
-  out dx, al
-
-  mov eax, ecx
-  shr eax, 0x10
-  mov edx, 0x1F5
-  out dx, al
-
-  mov eax, ecx
-  shr eax, 0x18
-  or  ax, 0xE0
-  mov edx, 0x1F6
-  out dx, al
-
-  mov ax, 0x20
-  mov edx, 0x1F7
-  out dx, al
-
-  call wait_disk
-
-  mov ecx, 0x80
-  mov edx, 0x1F0
-  cld
-  repnz ins DWORD PTR [edi], dx
-  ret
-
+.code32
+_code32:
+  mov ax, 0x10         # PROT_DS
+  mov ds, ax
+  mov es, ax
+  mov fs, ax
+  mov gs, ax
+  mov ss, ax
+  jmp _head64
 
-wait_disk:
-  mov edx, 0x1F7
-  in al, dx
-  and al, 0xC0
-  cmp al, 0x40
-  jne wait_disk
-  ret
+# The disk address packet of int 0x13, ah=0x42.
+.p2align 2
+dap:
+  .byte 0x10, 0
+  .word 1                  # sectors
+  .word 0, 0x7e0           # offset, segment
+  .quad 1                  # lba
 
 .p2align 2
 gdt:
@@ -177,3 +147,108 @@ gdt_desc:
 
 .org 510
 .word 0xaa55
+
+.section .boot16, "awx"
+.code16
+
+# MBInfo.flags: mem_low and mem_hi are valid, and are from e801.
+.set MB_INFO_MEMORY, 1 << 0
+.set MB_INFO_E801, 1 << 16
+.set MB_INFO_MMAP, 1 << 6
+
+# Without the e820 map, take the memory below 640K from int 0x12 and the
+# memory above 1M from e801, or from int 0x15 ah=0x88 on the older BIOSes.
+mem_setup:
+  test dword ptr [0x7000], MB_INFO_MMAP
+  jnz mem_done
+  int 0x12                                # ax = KiB below 640K
+  movzx eax, ax
+  mov dword ptr [0x7000 + 4], eax         # mem_low
+  xor cx, cx
+  xor dx, dx
+  mov ax, 0xe801
+  int 0x15
+  jc mem_88
+  jcxz e801_ax                            # Some BIOSes only report in ax, bx
+  mov ax, cx
+  mov bx, dx
+e801_ax:
+  movzx eax, ax                           # KiB between 1M and 16M
+  test eax, eax
+  jz mem_88
+  cmp eax, 15 * 1024
+  jb e801_done                            # A hole below 16M
+  movzx ebx, bx                           # 64K blocks above 16M
+  shl ebx, 6
+  add eax, ebx
+e801_done:
+  mov dword ptr [0x7000 + 8], eax         # mem_hi
+  or dword ptr [0x7000], MB_INFO_MEMORY | MB_INFO_E801
+  jmp mem_done
+mem_88:
+  mov ah, 0x88
+  int 0x15
+  jc mem_done
+  movzx eax, ax                           # KiB above 1M, up to 64M
+  test eax, eax
+  jz mem_done
+  mov dword ptr [0x7000 + 8], eax         # mem_hi
+  or dword ptr [0x7000], MB_INFO_MEMORY
+mem_done:
+
+# The VBE controller and mode information, in the scratch below 0x7c00.
+.set VBE_INFO, 0x7800
+.set VBE_MODE_INFO, 0x7a00
+# MBInfo.flags: VBE_MODE_INFO holds the mode that is set.
+.set MB_INFO_VBE, 1 << 11
+# offset_of!(bootimg::Header, video)
+.set HEADER_VIDEO, 896
+
+# Set the VBE mode with a direct color linear framebuffer of the resolution
+# and the depth that the image header asks for, and go back to vbe_return.
+# Keep the text mode if the header asks for none, or there is no such mode.
+vbe_setup:
+  mov eax, offset boot_end
+  shr eax, 4
+  mov gs, ax                              # gs:0 is the image header
+  cmp word ptr gs:[HEADER_VIDEO], 0       # width
+  je vbe_done
+  mov di, VBE_INFO
+  mov dword ptr [di], 0x32454256          # "VBE2"
+  mov ax, 0x4f00
+  int 0x10
+  cmp ax, 0x4f
+  jne vbe_done
+  lfs si, [VBE_INFO + 14]                 # VideoModePtr
+vbe_next_mode:
+  mov cx, word ptr fs:[si]
+  add si, 2
+  cmp cx, 0xffff
+  je vbe_done
+  mov di, VBE_MODE_INFO
+  mov ax, 0x4f01
+  int 0x10
+  cmp ax, 0x4f
+  jne vbe_next_mode
+  test byte ptr [VBE_MODE_INFO], 0x80     # ModeAttributes: linear framebuffer
+  jz vbe_next_mode
+  cmp byte ptr [VBE_MODE_INFO + 27], 6    # MemoryModel: direct color
+  jne vbe_next_mode
+  mov ax, word ptr gs:[HEADER_VIDEO]
+  cmp ax, word ptr [VBE_MODE_INFO + 18]   # XResolution
+  jne vbe_next_mode
+  mov ax, word ptr gs:[HEADER_VIDEO + 2]
+  cmp ax, word ptr [VBE_MODE_INFO + 20]   # YResolution
+  jne vbe_next_mode
+  mov al, byte ptr gs:[HEADER_VIDEO + 4]
+  cmp al, byte ptr [VBE_MODE_INFO + 25]   # BitsPerPixel
+  jne vbe_next_mode
+  mov bx, cx
+  or bx, 0x4000                           # with the linear framebuffer
+  mov ax, 0x4f02
+  int 0x10
+  cmp ax, 0x4f
+  jne vbe_next_mode
+  or dword ptr [0x7000], MB_INFO_VBE
+vbe_done:
+  jmp vbe_return
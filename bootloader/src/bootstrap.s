@@ -3,6 +3,10 @@
 .intel_syntax noprefix
 .code32
 
+# Switches from the protected mode to the long mode, and jumps to
+# bootstrap_long_entry. The includer defines bootstrap_long_entry and
+# BOOTSTRAP_OFFSET, the offset of the linked addresses from the physical
+# addresses that this runs at.
 _head64:
 verify_cpu:
   pushf
@@ -28,36 +32,36 @@ verify_cpu:
 
 # Now, setup the page table
 setup_pt:
-  lea edi, [boot_pml4e]
+  lea edi, [boot_pml4e - BOOTSTRAP_OFFSET]
   xor eax, eax
   mov ecx, 0x400
   rep stos dword ptr [edi]
 
   # setup the pdpts
-  lea edi, [boot_pml4e]
-  lea ebx, [boot_pdpt1]
+  lea edi, [boot_pml4e - BOOTSTRAP_OFFSET]
+  lea ebx, [boot_pdpt1 - BOOTSTRAP_OFFSET]
   or ebx, 0x3            # PTE_P | PTE_W
   mov [edi], ebx
-  lea ebx, [boot_pdpt2]
+  lea ebx, [boot_pdpt2 - BOOTSTRAP_OFFSET]
   or ebx, 0x3            # PTE_P | PTE_W
   mov [edi + 0x8], ebx
 
   # setup the pdpes
-  lea edi, [boot_pdpt1]
-  lea ebx, [boot_pde1]
+  lea edi, [boot_pdpt1 - BOOTSTRAP_OFFSET]
+  lea ebx, [boot_pde1 - BOOTSTRAP_OFFSET]
   or ebx, 0x3            # PTE_P | PTE_W
   mov [edi], ebx
 
-  lea edi, [boot_pdpt2]
-  lea ebx, [boot_pde2]
+  lea edi, [boot_pdpt2 - BOOTSTRAP_OFFSET]
+  lea ebx, [boot_pde2 - BOOTSTRAP_OFFSET]
   or ebx, 0x3            # PTE_P | PTE_W
   mov [edi], ebx
 
-  # setup the pdes with PTE_MBZ
+  # setup the pdes with PTE_MBZ, mapping the first 256 MiB at 0 and at
+  # 0x8000000000. The bootloader maps the rest before entering the kernel.
   mov ecx, 128
-  lea ebx, [boot_pde1]
-  lea edx, [boot_pde2]
-  add edx, 256
+  lea ebx, [boot_pde1 - BOOTSTRAP_OFFSET]
+  lea edx, [boot_pde2 - BOOTSTRAP_OFFSET]
   mov eax, 0x183         # PTE_P | PTE_W | PTE_MBZ
 
 looping:
@@ -71,7 +75,7 @@ looping:
   jne looping
 
   # load cr3
-  lea eax, [boot_pml4e]
+  lea eax, [boot_pml4e - BOOTSTRAP_OFFSET]
   mov cr3, eax
 
   # Enable the long mode
@@ -86,9 +90,9 @@ looping:
   mov cr0, eax
 
   # Jump to the long mode
-  lea eax, [gdt_desc64]
+  lea eax, [gdt_desc64 - BOOTSTRAP_OFFSET]
   lgdt [eax]
-  lea eax, [boot_main]
+  lea eax, [bootstrap_long_entry - BOOTSTRAP_OFFSET]
   push 0x8
   push eax
   retf
@@ -103,7 +107,7 @@ gdt64:
   .quad 0x00cf92000000ffff  # DATA SEGMENT64
 gdt_desc64:
   .word 0x17
-  .quad gdt64
+  .quad gdt64 - BOOTSTRAP_OFFSET
 
 .p2align 12
 .globl boot_pml4e
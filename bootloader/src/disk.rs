@@ -1,26 +1,140 @@
 use arch::PortMappedIO;
 
+// Registers of the primary ATA bus.
+const ATA_DATA: u16 = 0x1f0;
+const ATA_ERROR: u16 = 0x1f1;
+const ATA_COUNT: u16 = 0x1f2;
+const ATA_LBA0: u16 = 0x1f3;
+const ATA_LBA1: u16 = 0x1f4;
+const ATA_LBA2: u16 = 0x1f5;
+const ATA_DRIVE: u16 = 0x1f6;
+const ATA_COMMAND: u16 = 0x1f7;
+const ATA_STATUS: u16 = 0x1f7;
+const ATA_ALT_STATUS: u16 = 0x3f6;
+const ATA_CONTROL: u16 = 0x3f6;
+
+// Bits of the status register.
+const ATA_BSY: u8 = 0x80;
+const ATA_DRDY: u8 = 0x40;
+const ATA_DF: u8 = 0x20;
+const ATA_DRQ: u8 = 0x08;
+const ATA_ERR: u8 = 0x01;
+
+// Bits of the device control register.
+const ATA_SRST: u8 = 0x04;
+
+// The master drive in the LBA mode.
+const ATA_DRIVE_LBA: u8 = 0x40;
+const ATA_READ_SECTORS_EXT: u8 = 0x24;
+
+// Number of the status polls before giving up on the drive.
+const WAIT_LIMIT: u32 = 0x100000;
+// Number of the attempts of a read.
+const RETRIES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskError {
+    /// The drive stayed busy.
+    Timeout,
+    /// The drive reported a fault, with the contents of the error register.
+    Device(u8),
+}
+
 // opaque object for disk
 pub struct Disk;
 
 impl Disk {
     pub const BLOCK_SIZE: u32 = 512;
+    /// The most sectors that a read command transfers.
+    pub const MAX_SECTORS: u32 = 256;
 
+    // Poll the status until the drive is not busy and `ready` bits are set.
     #[inline(always)]
-    unsafe fn wait() {
-        while 0x1f7.read_u8() & 0xC0 != 0x40 {}
+    unsafe fn wait(ready: u8) -> Result<(), DiskError> {
+        for _ in 0..WAIT_LIMIT {
+            let status = ATA_STATUS.read_u8();
+            if status & ATA_BSY != 0 {
+                continue;
+            }
+            if status & (ATA_ERR | ATA_DF) != 0 {
+                return Err(DiskError::Device(ATA_ERROR.read_u8()));
+            }
+            if status & ready == ready {
+                return Ok(());
+            }
+        }
+        Err(DiskError::Timeout)
+    }
+
+    // Give the drive 400ns to update the status after a command.
+    #[inline(always)]
+    unsafe fn delay() {
+        for _ in 0..4 {
+            ATA_ALT_STATUS.read_u8();
+        }
+    }
+
+    // Reset the drives on the bus, which clears the error of the failed
+    // command that the status would report otherwise.
+    unsafe fn reset() -> Result<(), DiskError> {
+        ATA_CONTROL.write_u8(ATA_SRST);
+        // Hold the reset for 5us, as a status read takes 100ns or so.
+        for _ in 0..50 {
+            ATA_ALT_STATUS.read_u8();
+        }
+        ATA_CONTROL.write_u8(0);
+        Self::delay();
+        for _ in 0..WAIT_LIMIT {
+            if ATA_STATUS.read_u8() & (ATA_BSY | ATA_DRDY) == ATA_DRDY {
+                return Ok(());
+            }
+        }
+        Err(DiskError::Timeout)
+    }
+
+    unsafe fn read_once(
+        pa: u32,
+        lba: u64,
+        count: u32,
+    ) -> Result<(), DiskError> {
+        // The status is that of the selected drive.
+        ATA_DRIVE.write_u8(ATA_DRIVE_LBA);
+        Self::delay();
+        Self::wait(ATA_DRDY)?;
+        // LBA48 takes the high bytes first, and then the low bytes.
+        // 0 sectors means 65536 sectors.
+        ATA_COUNT.write_u8((count >> 8) as u8);
+        ATA_LBA0.write_u8((lba >> 24) as u8);
+        ATA_LBA1.write_u8((lba >> 32) as u8);
+        ATA_LBA2.write_u8((lba >> 40) as u8);
+        ATA_COUNT.write_u8(count as u8);
+        ATA_LBA0.write_u8(lba as u8);
+        ATA_LBA1.write_u8((lba >> 8) as u8);
+        ATA_LBA2.write_u8((lba >> 16) as u8);
+        ATA_COMMAND.write_u8(ATA_READ_SECTORS_EXT);
+
+        let mut addr = pa;
+        for _ in 0..count {
+            Self::delay();
+            Self::wait(ATA_DRQ)?;
+            ATA_DATA.read_u32s(addr, Self::BLOCK_SIZE / 4);
+            addr += Self::BLOCK_SIZE;
+        }
+        Ok(())
     }
 
+    /// Read `count` sectors from `lba` on to `pa`, retrying on failures.
+    /// `count` must be at most MAX_SECTORS.
     #[inline(never)]
-    pub unsafe fn read_sector(pa: u32, sect: u32) {
-        Self::wait();
-        0x1F2.write_u8(1);
-        0x1F3.write_u8((sect >> 0) as u8);
-        0x1F4.write_u8((sect >> 8) as u8);
-        0x1F5.write_u8((sect >> 16) as u8);
-        0x1F6.write_u8(((sect >> 24) | 0xE0) as u8);
-        0x1F7.write_u8(0x20);
-        Self::wait();
-        0x1F0.read_u32s(pa, Self::BLOCK_SIZE / 4);
+    pub unsafe fn read(pa: u32, lba: u64, count: u32) -> Result<(), DiskError> {
+        let mut result = Self::read_once(pa, lba, count);
+        for _ in 1..RETRIES {
+            if result.is_ok() {
+                break;
+            }
+            result =
+                Self::reset().and_then(|_| Self::read_once(pa, lba, count));
+        }
+        result
     }
 }
@@ -0,0 +1,87 @@
+use crate::lang::{self, Failure};
+use crate::readseg;
+use bootimg::{Entry, Header, ENTRY_CMDLINE, IMAGE_SIGNED, SECTOR_SIZE};
+use bootinfo::BootInfo;
+use core::mem::size_of;
+use crypto::ed25519::{self, PUBLIC_KEY_SIZE};
+use crypto::sha256;
+
+// The hex-encoded Ed25519 public key, given at the build time. If there is
+// one, the bootloader boots only the images signed with it.
+const PUBLIC_KEY: Option<&str> = option_env!("BOOT_PUBLIC_KEY");
+
+// readseg() works on whole sectors.
+#[repr(C, align(512))]
+struct Sector<T>(T);
+
+static mut HEADER: Sector<Header> = Sector(Header::new());
+static mut CMDLINE: Sector<[u8; SECTOR_SIZE as usize]> =
+    Sector([0; SECTOR_SIZE as usize]);
+
+fn public_key() -> Option<[u8; PUBLIC_KEY_SIZE]> {
+    let hex = PUBLIC_KEY.filter(|hex| !hex.is_empty())?.as_bytes();
+    let nibble = |c: u8| match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => lang::fail(Failure::BadSignature),
+    };
+    if hex.len() != PUBLIC_KEY_SIZE * 2 {
+        lang::fail(Failure::BadSignature)
+    }
+    let mut key = [0; PUBLIC_KEY_SIZE];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = nibble(hex[2 * i]) << 4 | nibble(hex[2 * i + 1]);
+    }
+    Some(key)
+}
+
+/// Read the image header at `offset` of the disk. The bootloader can't go
+/// on unless it is intact, and signed if the bootloader has a public key.
+pub unsafe fn read_header(offset: u32) -> &'static mut Header {
+    let header = &mut HEADER.0;
+    readseg(header as *mut _ as u32, size_of::<Header>() as u32, offset);
+    if !header.is_valid() {
+        lang::fail(Failure::BadImage)
+    }
+    if let Some(key) = public_key() {
+        if header.flags & IMAGE_SIGNED == 0
+            || !ed25519::verify(
+                &key,
+                header.message().as_bytes(),
+                &header.signature,
+            )
+        {
+            lang::fail(Failure::BadSignature)
+        }
+    }
+    header
+}
+
+/// Check that `data`, which was read from `entry`, is what the image
+/// header says.
+pub fn check_digest(entry: &Entry, data: &[u8]) {
+    if data.len() != entry.size as usize || sha256::digest(data) != entry.sha256
+    {
+        lang::fail(Failure::BadDigest)
+    }
+}
+
+/// Read the command line, if any, and hand it over to the kernel.
+pub unsafe fn load_cmdline(header: &Header, info: &mut BootInfo) {
+    if let Some(entry) = header.find(ENTRY_CMDLINE) {
+        let cmdline = &mut CMDLINE.0;
+        let len = entry.size as usize;
+        if len > cmdline.len() {
+            lang::fail(Failure::BadImage)
+        }
+        readseg(
+            cmdline.as_mut_ptr() as u32,
+            len as u32,
+            entry.offset() as u32,
+        );
+        check_digest(entry, &cmdline[..len]);
+        info.cmdline_addr = cmdline.as_ptr() as u64;
+        info.cmdline_len = len as u32;
+    }
+}
@@ -0,0 +1,169 @@
+use bootinfo::*;
+use core::mem::size_of;
+
+// The multiboot information that boot.s has filled in from the BIOS.
+const MB_INFO: u64 = 0x7000;
+// MBInfo.flags: mem_low and mem_hi, boot_dev and the memory map are
+// available.
+const MB_INFO_MEMORY: u32 = 1 << 0;
+const MB_INFO_BOOT_DEV: u32 = 1 << 1;
+const MB_INFO_MMAP: u32 = 1 << 6;
+// MBInfo.flags: mem_hi is from e801 rather than int 0x15 ah=0x88.
+const MB_INFO_E801: u32 = 1 << 16;
+// MBInfo.flags: boot.s has set the mode in VBE_MODE_INFO.
+const MB_INFO_VBE: u32 = 1 << 11;
+const VBE_MODE_INFO: u64 = 0x7a00;
+
+// E820_MAX of boot.s, which keeps the e820 map below VBE_INFO.
+const MMAP_MAX: usize = 64;
+
+#[repr(C)]
+struct MBInfo {
+    flags: u32,
+    mem_low: u32,
+    mem_hi: u32,
+    boot_dev: u32,
+    _unused: [u32; 7],
+    mmap_len: u32,
+    mmap_addr: u32,
+}
+
+// The VBE mode information, up to the physical address of the framebuffer.
+#[repr(C, packed)]
+struct VbeModeInfo {
+    attributes: u16,
+    _windows: [u8; 14],
+    pitch: u16,
+    width: u16,
+    height: u16,
+    _char_size: [u8; 2],
+    _planes: u8,
+    bpp: u8,
+    _banks: u8,
+    _memory_model: u8,
+    _bank_size: u8,
+    _image_pages: u8,
+    _reserved: u8,
+    red_size: u8,
+    red_pos: u8,
+    green_size: u8,
+    green_pos: u8,
+    blue_size: u8,
+    blue_pos: u8,
+    _rsvd_mask: [u8; 2],
+    _direct_color: u8,
+    framebuffer: u32,
+}
+
+impl From<&VbeModeInfo> for Framebuffer {
+    fn from(mode: &VbeModeInfo) -> Framebuffer {
+        Framebuffer {
+            addr: mode.framebuffer as u64,
+            pitch: mode.pitch as u32,
+            width: mode.width as u32,
+            height: mode.height as u32,
+            bpp: mode.bpp,
+            red_pos: mode.red_pos,
+            red_size: mode.red_size,
+            green_pos: mode.green_pos,
+            green_size: mode.green_size,
+            blue_pos: mode.blue_pos,
+            blue_size: mode.blue_size,
+            _reserved: 0,
+        }
+    }
+}
+
+#[repr(C)]
+struct E820Entry {
+    size: u32,
+    mem_lo: u32,
+    mem_hi: u32,
+    len_lo: u32,
+    len_hi: u32,
+    type_: u32,
+}
+
+impl From<&E820Entry> for MemoryEntry {
+    fn from(mm: &E820Entry) -> MemoryEntry {
+        MemoryEntry {
+            addr: (mm.mem_lo as u64) | ((mm.mem_hi as u64) << 32),
+            len: (mm.len_lo as u64) | ((mm.len_hi as u64) << 32),
+            kind: mm.type_,
+            _reserved: 0,
+        }
+    }
+}
+
+const EMPTY: MemoryEntry = MemoryEntry {
+    addr: 0,
+    len: 0,
+    kind: 0,
+    _reserved: 0,
+};
+
+/// The boot information handed over to the kernel.
+pub static mut BOOT_INFO: BootInfo = BootInfo::new();
+static mut MEMORY_MAP: [MemoryEntry; MMAP_MAX] = [EMPTY; MMAP_MAX];
+
+/// Fill in the memory map, the boot drive and the framebuffer that boot.s
+/// got from the BIOS.
+pub unsafe fn collect_bios_info(info: &mut BootInfo) {
+    let mb_info = &*(MB_INFO as *const MBInfo);
+    info.boot_drive = if mb_info.flags & MB_INFO_BOOT_DEV != 0 {
+        // The drive number is the highest byte.
+        mb_info.boot_dev >> 24
+    } else {
+        NO_BOOT_DRIVE
+    };
+    if mb_info.flags & MB_INFO_VBE != 0 {
+        info.framebuffer =
+            Framebuffer::from(&*(VBE_MODE_INFO as *const VbeModeInfo));
+    }
+    if mb_info.flags & MB_INFO_MMAP != 0 {
+        let entries =
+            (mb_info.mmap_len as usize / size_of::<E820Entry>()).min(MMAP_MAX);
+        let e820 = mb_info.mmap_addr as *const E820Entry;
+        for i in 0..entries {
+            MEMORY_MAP[i] = MemoryEntry::from(&*e820.add(i));
+        }
+        info.mmap_addr = MEMORY_MAP.as_ptr() as u64;
+        info.mmap_count = entries as u32;
+        info.mmap_source = MMAP_SOURCE_E820;
+    } else if mb_info.flags & MB_INFO_MEMORY != 0 {
+        // Only the sizes of the conventional memory and of the memory
+        // from 1 MiB on are known.
+        MEMORY_MAP[0] = usable(0, mb_info.mem_low as u64 * 1024);
+        MEMORY_MAP[1] = usable(0x100000, mb_info.mem_hi as u64 * 1024);
+        info.mmap_addr = MEMORY_MAP.as_ptr() as u64;
+        info.mmap_count = 2;
+        info.mmap_source = if mb_info.flags & MB_INFO_E801 != 0 {
+            MMAP_SOURCE_E801
+        } else {
+            MMAP_SOURCE_LEGACY
+        };
+    }
+}
+
+fn usable(addr: u64, len: u64) -> MemoryEntry {
+    MemoryEntry {
+        addr,
+        len,
+        kind: MEMORY_USABLE,
+        _reserved: 0,
+    }
+}
+
+/// The end of the usable memory that holds `start`, up to `max`. Without a
+/// memory map, the memory is assumed to be there up to `max`.
+pub unsafe fn usable_end(info: &BootInfo, start: u64, max: u64) -> u64 {
+    let map = info.memory_map(0);
+    if map.is_empty() {
+        return max;
+    }
+    map.iter()
+        .filter(|entry| entry.kind == MEMORY_USABLE && entry.addr <= start)
+        .map(|entry| entry.addr.saturating_add(entry.len))
+        .find(|&end| end > start)
+        .map_or(start, |end| end.min(max))
+}
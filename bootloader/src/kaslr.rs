@@ -0,0 +1,140 @@
+//! Kernel address space layout randomization.
+//!
+//! The kernel is linked with --emit-relocs, which keeps its relocations in
+//! the image. The bootloader loads the kernel at a random slide from its
+//! linked addresses, the same for the physical and the virtual ones as the
+//! kernel runs in the direct map, and moves the absolute addresses in the
+//! kernel by the slide.
+
+use arch::PortMappedIO;
+use bootinfo::BootInfo;
+use crypto::sha256;
+use elf::fmt::{SHFlags64, SHType, SectHeader64};
+use elf::reloc::Relocator;
+use elf::section::{SHN_ABS, SHN_UNDEF};
+use elf::ELF;
+
+/// The alignment of the slide, that of the large pages.
+pub const SLIDE_ALIGN: u64 = 0x200000;
+
+// cpuid 1 ecx: rdrand.
+const CPUID_RDRAND: u32 = 1 << 30;
+// rdrand may run out of the entropy for a moment.
+const RDRAND_RETRIES: usize = 10;
+
+// The index and the data ports of the CMOS, which holds the RTC.
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+// The seconds, the minutes, the hours, the day, the month and the year.
+const RTC_REGS: [u8; 6] = [0x0, 0x2, 0x4, 0x7, 0x8, 0x9];
+
+fn rdrand() -> Option<u64> {
+    let ecx: u32;
+    unsafe {
+        asm!("cpuid" : "={ecx}"(ecx) : "{eax}"(1u32)
+                     : "ebx", "edx" : "volatile");
+    }
+    if ecx & CPUID_RDRAND == 0 {
+        return None;
+    }
+    for _ in 0..RDRAND_RETRIES {
+        let (v, ok): (u64, u8);
+        unsafe {
+            asm!("rdrand $0\n\t
+                  setc $1" : "=r"(v), "=r"(ok) : : "cc" : "volatile");
+        }
+        if ok != 0 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+fn rdtsc() -> u64 {
+    let (lo, hi): (u32, u32);
+    unsafe {
+        asm!("rdtsc" : "={eax}"(lo), "={edx}"(hi) : : : "volatile");
+    }
+    (hi as u64) << 32 | lo as u64
+}
+
+/// A random number from rdrand, or from the tsc and the rtc mixed up if
+/// the cpu has no rdrand.
+pub fn random() -> u64 {
+    if let Some(v) = rdrand() {
+        return v;
+    }
+    let mut seed = [0; 16];
+    seed[..8].copy_from_slice(&rdtsc().to_le_bytes());
+    for (byte, &reg) in seed[8..].iter_mut().zip(RTC_REGS.iter()) {
+        CMOS_INDEX.write_u8(reg);
+        *byte = CMOS_DATA.read_u8();
+    }
+    let mut v = [0; 8];
+    v.copy_from_slice(&sha256::digest(&seed)[..8]);
+    u64::from_le_bytes(v)
+}
+
+/// Whether the command line turns KASLR off with `nokaslr`.
+pub unsafe fn is_disabled(info: &BootInfo) -> bool {
+    info.cmdline(0).map_or(false, |cmdline| {
+        cmdline
+            .split_ascii_whitespace()
+            .any(|option| option == "nokaslr")
+    })
+}
+
+/// Pick a random slide of SLIDE_ALIGN from `min` up to `max`, or 0 if
+/// there is none.
+pub fn choose_slide(min: u64, max: u64) -> u64 {
+    let min = (min + SLIDE_ALIGN - 1) & !(SLIDE_ALIGN - 1);
+    let max = max & !(SLIDE_ALIGN - 1);
+    if min > max {
+        return 0;
+    }
+    let slots = (max - min) / SLIDE_ALIGN + 1;
+    min + random() % slots * SLIDE_ALIGN
+}
+
+// The RELA sections that apply to the loaded sections. The others, such as
+// those of the debug info, don't move with the kernel.
+fn rela_sections<'a>(elf: &'a ELF) -> impl Iterator<Item = SectHeader64> + 'a {
+    elf.shdrs().filter(move |shdr| {
+        shdr.kind() == Some(SHType::RELA)
+            && elf.section(shdr.sh_info).map_or(false, |target| {
+                target.flags().contains(SHFlags64::ALLOC)
+            })
+    })
+}
+
+/// Whether `elf` keeps the relocations to move its loaded sections.
+pub fn is_relocatable(elf: &ELF) -> bool {
+    rela_sections(elf).next().is_some()
+}
+
+/// Move the absolute addresses in the kernel `elf`, which is loaded in
+/// `mem` from the linked virtual address `vaddr_base`, by `slide`.
+pub fn relocate(
+    elf: &ELF,
+    mem: &mut [u8],
+    vaddr_base: u64,
+    slide: u64,
+) -> Option<()> {
+    let mut relocator = Relocator::new(mem, vaddr_base, slide);
+    for shdr in rela_sections(elf) {
+        let symtab = elf.symbol_table(&elf.section(shdr.sh_link)?)?;
+        for rela in elf.relas(&shdr)? {
+            relocator
+                .apply(&rela, |index| {
+                    let sym = symtab.get(index as usize)?;
+                    match sym.st_shndx {
+                        // Only the weak symbols stay undefined, at 0.
+                        SHN_UNDEF | SHN_ABS => Some(sym.st_value),
+                        _ => Some(sym.st_value + slide),
+                    }
+                })
+                .ok()?;
+        }
+    }
+    Some(())
+}
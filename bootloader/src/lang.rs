@@ -1,7 +1,30 @@
 use arch::PortMappedIO;
 
+/// Failure codes that the bootloader reports through the isa-debug-exit
+/// port of qemu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Failure {
+    Panic = 0x10,
+    DiskTimeout = 0x11,
+    DiskError = 0x12,
+    BadImage = 0x13,
+    BadDigest = 0x14,
+    BadSignature = 0x15,
+    BadRelocation = 0x16,
+    BadPhdrs = 0x17,
+    BadLayout = 0x18,
+    NoRoomForModules = 0x19,
+    NoRoomForPageTables = 0x1a,
+    BadElf = 0x1b,
+}
+
+pub fn fail(code: Failure) -> ! {
+    0xf4.write_u32(code as u32);
+    loop {}
+}
+
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
-    0xf4.write_u32(0x10);
-    loop {}
+    fail(Failure::Panic)
 }
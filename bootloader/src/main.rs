@@ -4,13 +4,31 @@
 #![no_main]
 
 mod disk;
+mod image;
+mod info;
+mod kaslr;
 mod lang;
+mod modules;
+mod paging;
+mod symbols;
 
-use disk::Disk;
+use arch::PAGE_SIZE;
+use bootimg::ENTRY_KERNEL;
+use bootinfo::{PhysRange, BOOTINFO_MAGIC};
+use core::mem::size_of;
+use core::{ptr, slice};
+use disk::{Disk, DiskError};
+use elf::fmt::{EIClass, ELFHeader64};
+use elf::layout::AddrSpace;
 use elf::ELF;
+use lang::Failure;
 
 global_asm!(include_str!("boot.s"));
-global_asm!(include_str!("bootstrap.s"));
+global_asm!(concat!(
+    ".set BOOTSTRAP_OFFSET, 0\n",
+    ".set bootstrap_long_entry, boot_main\n",
+    include_str!("bootstrap.s")
+));
 
 extern "C" {
     static boot_start: u64;
@@ -18,15 +36,32 @@ extern "C" {
 }
 
 const KERN_ELF_BASE: u64 = 0x20000;
+// The front of the kernel image and its symbols are staged within the
+// conventional memory below the EBDA.
+const KERN_ELF_END: u64 = 0x80000;
+// The kernel and the modules are loaded above the kernel stack, which grows
+// down from 0x200000, and within the memory that bootstrap.s identity maps.
+const KERN_LOAD_MIN: u64 = 0x200000;
+const KERN_LOAD_MAX: u64 = 0x10000000;
+// The bootloader stack grows down from 0x7000, and the free conventional
+// memory starts from 0x500.
+const BOOT_STACK_BOTTOM: u64 = 0x500;
 
 unsafe fn readseg(pa: u32, count: u32, offset: u32) {
+    let end = pa + count;
     let mut addr: u32 = pa & !(Disk::BLOCK_SIZE - 1);
     // FIXME: add round_down and change to it.
-    let mut sect: u32 = offset / Disk::BLOCK_SIZE;
-    while addr < pa + count {
-        Disk::read_sector(addr, sect);
-        addr += Disk::BLOCK_SIZE;
-        sect += 1;
+    let mut sect = (offset / Disk::BLOCK_SIZE) as u64;
+    while addr < end {
+        let sects = ((end - addr + Disk::BLOCK_SIZE - 1) / Disk::BLOCK_SIZE)
+            .min(Disk::MAX_SECTORS);
+        match Disk::read(addr, sect, sects) {
+            Ok(()) => {}
+            Err(DiskError::Timeout) => lang::fail(Failure::DiskTimeout),
+            Err(DiskError::Device(_)) => lang::fail(Failure::DiskError),
+        }
+        addr += sects * Disk::BLOCK_SIZE;
+        sect += sects as u64;
     }
 }
 
@@ -34,30 +69,184 @@ unsafe fn readseg(pa: u32, count: u32, offset: u32) {
 unsafe extern "C" fn boot_main() -> ! {
     let bootloader_start = &boot_start as *const _ as u64;
     let bootloader_end = &boot_end as *const _ as u64;
-    let kern_start = (bootloader_end - bootloader_start) as u32;
-
-    readseg(KERN_ELF_BASE as u32, 0x1000, kern_start);
-
-    match ELF::new(KERN_ELF_BASE as *const u8) {
-        Ok(elf) => {
-            // Currently, bootloader assumes phdr lies on the first page.
-            // This should be fixed later.
-            for phdr in elf.phdrs() {
-                readseg(
-                    phdr.p_paddr as u32,
-                    phdr.p_filesz as u32,
-                    phdr.p_offset as u32 + kern_start,
+    let boot_info = &mut info::BOOT_INFO;
+    info::collect_bios_info(boot_info);
+    // The image header follows the bootloader, and tells where the rest is.
+    let header = image::read_header((bootloader_end - bootloader_start) as u32);
+    let kernel = match header.find(ENTRY_KERNEL) {
+        Some(entry) if entry.size as u64 <= KERN_LOAD_MAX - KERN_LOAD_MIN => {
+            *entry
+        }
+        _ => lang::fail(Failure::BadImage),
+    };
+    // Read and check the whole kernel image in the load window first, and
+    // take all of the kernel from it, not from the disk.
+    readseg(KERN_LOAD_MIN as u32, kernel.size, kernel.offset() as u32);
+    let kern_image =
+        slice::from_raw_parts(KERN_LOAD_MIN as *const u8, kernel.size as usize);
+    image::check_digest(&kernel, kern_image);
+    image::load_cmdline(header, boot_info);
+    boot_info.bootloader = PhysRange::new(BOOT_STACK_BOTTOM, bootloader_end);
+
+    // Copy the ELF header and the program header table out of the checked
+    // image, which the segments may be loaded over.
+    if kern_image.len() < size_of::<ELFHeader64>() {
+        lang::fail(Failure::BadElf)
+    }
+    let hdr = ptr::read_unaligned(kern_image.as_ptr() as *const ELFHeader64);
+    let phdrs_end = hdr
+        .e_phoff
+        .checked_add(hdr.e_phnum as u64 * hdr.e_phentsize as u64);
+    let len = match phdrs_end {
+        Some(end)
+            if end <= KERN_ELF_END - KERN_ELF_BASE
+                && end <= kern_image.len() as u64 =>
+        {
+            end.max(size_of::<ELFHeader64>() as u64)
+        }
+        _ => lang::fail(Failure::BadPhdrs),
+    };
+    ptr::copy_nonoverlapping(
+        kern_image.as_ptr(),
+        KERN_ELF_BASE as *mut u8,
+        len as usize,
+    );
+    // The symbols are only for the backtraces, so boot without them if
+    // they can't be staged.
+    let staged =
+        symbols::load_symbols(&hdr, kern_image, KERN_ELF_BASE + len, boot_info);
+    boot_info.staging =
+        PhysRange::new(KERN_ELF_BASE, staged.unwrap_or(KERN_ELF_BASE + len));
+
+    let image = slice::from_raw_parts(KERN_ELF_BASE as *const u8, len as usize);
+    match ELF::parse_segments(image) {
+        // The kernel runs in the long mode.
+        Ok(elf) if elf.class() == EIClass::Bit64 => {
+            // Refuse to scribble over the memory outside of the window.
+            if elf
+                .validate_layout(
+                    AddrSpace::Physical,
+                    KERN_LOAD_MIN..KERN_LOAD_MAX,
                 )
+                .is_err()
+            {
+                lang::fail(Failure::BadLayout)
+            }
+            let linked = elf.phdrs().filter(|phdr| phdr.is_load()).fold(
+                PhysRange::new(u64::MAX, 0),
+                |range, phdr| {
+                    PhysRange::new(
+                        range.start.min(phdr.p_paddr),
+                        range.end.max(phdr.p_paddr + phdr.p_memsz),
+                    )
+                },
+            );
+            let limit =
+                info::usable_end(boot_info, KERN_LOAD_MIN, KERN_LOAD_MAX);
+            // Slide the kernel past its checked image, which the relocations
+            // are read from, leaving the room for the modules and the page
+            // tables that follow it.
+            let relocatable = ELF::parse(kern_image)
+                .ok()
+                .filter(|image| kaslr::is_relocatable(image))
+                .filter(|_| !kaslr::is_disabled(boot_info));
+            let slide = match relocatable {
+                Some(_) => kaslr::choose_slide(
+                    (KERN_LOAD_MIN + kernel.size as u64)
+                        .saturating_sub(linked.start),
+                    limit.saturating_sub(
+                        linked.end
+                            + modules::modules_size(header)
+                            + paging::tables_size(boot_info)
+                            + PAGE_SIZE,
+                    ),
+                ),
+                None => 0,
+            };
+            // A kernel loaded at its linked addresses may land on its image,
+            // so move the image past it first.
+            let kern_image = if linked.start + slide
+                < KERN_LOAD_MIN + kern_image.len() as u64
+            {
+                let to =
+                    (linked.end + slide + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+                if to + kern_image.len() as u64 > limit {
+                    lang::fail(Failure::BadLayout)
+                }
+                ptr::copy(kern_image.as_ptr(), to as *mut u8, kern_image.len());
+                slice::from_raw_parts(to as *const u8, kern_image.len())
+            } else {
+                kern_image
+            };
+            for phdr in elf.phdrs().filter(|phdr| phdr.is_load()) {
+                let start = phdr.p_offset as usize;
+                let data = match start
+                    .checked_add(phdr.p_filesz as usize)
+                    .and_then(|end| kern_image.get(start..end))
+                {
+                    Some(data) => data,
+                    None => lang::fail(Failure::BadElf),
+                };
+                ptr::copy_nonoverlapping(
+                    data.as_ptr(),
+                    (phdr.p_paddr + slide) as *mut u8,
+                    data.len(),
+                );
+            }
+            boot_info.kernel =
+                PhysRange::new(linked.start + slide, linked.end + slide);
+            boot_info.kernel_slide = slide;
+            // Zero the rest of the segments (the BSS).
+            for phdr in elf.phdrs().filter(|phdr| phdr.is_load()) {
+                ptr::write_bytes(
+                    (phdr.p_paddr + phdr.p_filesz + slide) as *mut u8,
+                    0,
+                    (phdr.p_memsz - phdr.p_filesz) as usize,
+                );
+            }
+            if let Some(image) = relocatable.filter(|_| slide != 0) {
+                let vaddr_base = elf
+                    .phdrs()
+                    .filter(|phdr| phdr.is_load())
+                    .min_by_key(|phdr| phdr.p_paddr)
+                    .map_or(0, |phdr| phdr.p_vaddr);
+                let mem = slice::from_raw_parts_mut(
+                    boot_info.kernel.start as *mut u8,
+                    boot_info.kernel.len() as usize,
+                );
+                if kaslr::relocate(&image, mem, vaddr_base, slide).is_none() {
+                    lang::fail(Failure::BadRelocation)
+                }
+            }
+            // The modules follow the kernel, and the page tables follow
+            // them.
+            let modules_end = match modules::load_modules(
+                header,
+                boot_info.kernel.end,
+                limit,
+                boot_info,
+            ) {
+                Some(end) => end,
+                None => lang::fail(Failure::NoRoomForModules),
+            };
+            match paging::build_direct_map(modules_end, limit, boot_info) {
+                Some(pml4) => paging::switch_to(pml4),
+                None => lang::fail(Failure::NoRoomForPageTables),
             }
 
             // Now, the kernel loaded into the memory.
             // The only remaining thing is to jump into the kernel entry
+            // with a null frame pointer, which ends the kernel backtraces,
+            // and the boot information in rdi and rsi.
             asm!("mov $$0x200000, %rsp\n\t
+                  xor %rbp, %rbp\n\t
                   jmpq *%rax" : :
-                 "{rax}"(elf.entry())
+                 "{rax}"(elf.entry() + slide),
+                 "{rdi}"(BOOTINFO_MAGIC),
+                 "{rsi}"(boot_info as *const _ as u64)
                  : : "volatile");
             ::core::hint::unreachable_unchecked()
         }
-        Err(_) => panic!(),
+        _ => lang::fail(Failure::BadElf),
     }
 }
@@ -0,0 +1,64 @@
+use crate::image;
+use crate::readseg;
+use arch::page_up;
+use bootimg::{Header, ENTRIES_MAX, ENTRY_MODULE, NAME_MAX};
+use bootinfo::{BootInfo, Module, PhysRange};
+use core::slice;
+
+static mut MODULES: [Module; ENTRIES_MAX] = [Module {
+    range: PhysRange::new(0, 0),
+    cmdline: 0,
+    _reserved: 0,
+}; ENTRIES_MAX];
+
+/// The memory that `load_modules` takes for the modules in the image.
+pub fn modules_size(header: &Header) -> u64 {
+    header
+        .entries()
+        .iter()
+        .filter(|entry| entry.kind == ENTRY_MODULE)
+        .map(|entry| page_up!(entry.size as u64))
+        .sum()
+}
+
+/// Load the modules in the image page by page from `base` up to `limit`,
+/// and report them in `info`. The name of a module is its command line.
+///
+/// Returns the end of the loaded modules.
+pub unsafe fn load_modules(
+    header: &mut Header,
+    base: u64,
+    limit: u64,
+    info: &mut BootInfo,
+) -> Option<u64> {
+    let count = header.count as usize;
+    let mut next = base;
+    let mut loaded = 0;
+    for entry in header.entries[..count]
+        .iter_mut()
+        .filter(|entry| entry.kind == ENTRY_MODULE)
+    {
+        let start = page_up!(next);
+        let end = start.checked_add(entry.size as u64)?;
+        if end > limit {
+            return None;
+        }
+        readseg(start as u32, entry.size, entry.offset() as u32);
+        image::check_digest(
+            entry,
+            slice::from_raw_parts(start as *const u8, entry.size as usize),
+        );
+        // The name is the command line, so make sure that it terminates.
+        entry.name[NAME_MAX - 1] = 0;
+        MODULES[loaded] = Module {
+            range: PhysRange::new(start, end),
+            cmdline: entry.name.as_ptr() as u64,
+            _reserved: 0,
+        };
+        loaded += 1;
+        next = end;
+    }
+    info.mods_count = loaded as u32;
+    info.mods_addr = MODULES.as_ptr() as u64;
+    Some(next)
+}
@@ -0,0 +1,112 @@
+//! The direct map that the kernel starts on.
+//!
+//! bootstrap.s only maps the first 256 MiB, which is enough for the
+//! bootloader itself. Before jumping to the kernel, the bootloader maps all
+//! the physical memory both at 0 and at KERNBASE, with the 1 GiB pages if
+//! the cpu has them and with the 2 MiB pages otherwise.
+
+use arch::{page_up, PAGE_SIZE};
+use bootinfo::{BootInfo, PhysRange};
+use core::ptr;
+
+/// Where the kernel sees the physical memory.
+pub const KERNBASE: u64 = 0x80_0000_0000;
+
+const PTE_P: u64 = 1 << 0;
+const PTE_W: u64 = 1 << 1;
+const PTE_PS: u64 = 1 << 7;
+
+const ENTRIES: u64 = 512;
+const PAGE_SIZE_2M: u64 = 1 << 21;
+const PAGE_SIZE_1G: u64 = 1 << 30;
+// The devices and the framebuffer sit below 4 GiB, where the memory map
+// need not report them.
+const MAP_MIN: u64 = 4 << 30;
+// What a page directory pointer table maps.
+const MAP_MAX: u64 = ENTRIES * PAGE_SIZE_1G;
+
+// cpuid 0x80000001 edx: the 1 GiB pages.
+const CPUID_PDPE1GB: u32 = 1 << 26;
+
+fn has_1g_pages() -> bool {
+    let edx: u32;
+    unsafe {
+        asm!("cpuid" : "={edx}"(edx) : "{eax}"(0x8000_0001u32)
+                     : "ebx", "ecx" : "volatile");
+    }
+    edx & CPUID_PDPE1GB != 0
+}
+
+// The GiBs that the direct map covers: the memory map and the framebuffer,
+// and at least the first 4 GiB.
+unsafe fn direct_map_gigs(info: &BootInfo) -> u64 {
+    let fb = &info.framebuffer;
+    let end = info
+        .memory_map(0)
+        .iter()
+        .map(|entry| entry.addr.saturating_add(entry.len))
+        .chain(Some(fb.addr + fb.pitch as u64 * fb.height as u64))
+        .fold(MAP_MIN, u64::max)
+        .min(MAP_MAX);
+    (end + PAGE_SIZE_1G - 1) >> 30
+}
+
+/// The memory that `build_direct_map` takes for the page tables.
+pub unsafe fn tables_size(info: &BootInfo) -> u64 {
+    let pds = if has_1g_pages() {
+        0
+    } else {
+        direct_map_gigs(info)
+    };
+    (2 + pds) * PAGE_SIZE
+}
+
+/// Build the page tables of the direct map page by page from `base` up to
+/// `limit`, and report them in `info`.
+///
+/// Returns the physical address of the PML4.
+pub unsafe fn build_direct_map(
+    base: u64,
+    limit: u64,
+    info: &mut BootInfo,
+) -> Option<u64> {
+    let gigs = direct_map_gigs(info);
+    let huge = has_1g_pages();
+
+    let pml4 = page_up!(base);
+    let pdpt = pml4 + PAGE_SIZE;
+    let pds = pdpt + PAGE_SIZE;
+    let tables_end = if huge { pds } else { pds + gigs * PAGE_SIZE };
+    if tables_end > limit {
+        return None;
+    }
+    ptr::write_bytes(pml4 as *mut u8, 0, (tables_end - pml4) as usize);
+
+    // The identity map and the one at KERNBASE share the pdpt.
+    let pml4e = pml4 as *mut u64;
+    *pml4e = pdpt | PTE_P | PTE_W;
+    *pml4e.add((KERNBASE >> 39) as usize) = pdpt | PTE_P | PTE_W;
+    let pdpte = pdpt as *mut u64;
+    for i in 0..gigs {
+        let pa = i * PAGE_SIZE_1G;
+        *pdpte.add(i as usize) = if huge {
+            pa | PTE_P | PTE_W | PTE_PS
+        } else {
+            let pd = pds + i * PAGE_SIZE;
+            let pde = pd as *mut u64;
+            for j in 0..ENTRIES {
+                *pde.add(j as usize) =
+                    (pa + j * PAGE_SIZE_2M) | PTE_P | PTE_W | PTE_PS;
+            }
+            pd | PTE_P | PTE_W
+        };
+    }
+    info.page_tables = PhysRange::new(pml4, tables_end);
+    Some(pml4)
+}
+
+/// Switch to the page tables at `pml4`, which must identity map the
+/// bootloader.
+pub unsafe fn switch_to(pml4: u64) {
+    asm!("mov $0, %cr3" : : "r"(pml4) : "memory" : "volatile");
+}
@@ -0,0 +1,66 @@
+use crate::KERN_ELF_END;
+use bootinfo::BootInfo;
+use core::mem::size_of;
+use core::ptr;
+use elf::fmt::{ELFHeader64, SHType, SectHeader64};
+
+struct Stage<'a> {
+    next: u64,
+    image: &'a [u8],
+}
+
+impl Stage<'_> {
+    // Copy `size` bytes at `offset` of the kernel image into the staging
+    // area.
+    unsafe fn load(&mut self, offset: u64, size: u64) -> Option<u64> {
+        let start = offset as usize;
+        let data = self.image.get(start..start.checked_add(size as usize)?)?;
+        let pa = (self.next + 7) & !7;
+        if pa.checked_add(size)? > KERN_ELF_END {
+            return None;
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), pa as *mut u8, data.len());
+        self.next = pa + size;
+        Some(pa)
+    }
+}
+
+/// Copy the section headers and the non-loadable string and symbol tables
+/// out of the kernel `image` to `base` on, and report them in `info`. The
+/// kernel uses them to symbolize its backtraces.
+///
+/// Returns the end of the loaded tables.
+pub unsafe fn load_symbols(
+    hdr: &ELFHeader64,
+    image: &[u8],
+    base: u64,
+    info: &mut BootInfo,
+) -> Option<u64> {
+    if hdr.e_shnum == 0 || hdr.e_shentsize as usize != size_of::<SectHeader64>()
+    {
+        return None;
+    }
+    let mut stage = Stage { next: base, image };
+    let shdrs = stage
+        .load(hdr.e_shoff, hdr.e_shnum as u64 * hdr.e_shentsize as u64)?
+        as *mut SectHeader64;
+
+    for i in 0..hdr.e_shnum as usize {
+        let mut shdr = ptr::read_unaligned(shdrs.add(i));
+        match shdr.kind() {
+            Some(SHType::SYMTAB) | Some(SHType::STRTAB)
+                if shdr.sh_addr == 0 =>
+            {
+                shdr.sh_addr = stage.load(shdr.sh_offset, shdr.sh_size)?;
+                ptr::write_unaligned(shdrs.add(i), shdr);
+            }
+            _ => {}
+        }
+    }
+
+    info.shdr_num = hdr.e_shnum as u32;
+    info.shdr_size = hdr.e_shentsize as u32;
+    info.shdr_addr = shdrs as u64;
+    info.shdr_shndx = hdr.e_shstrndx as u32;
+    Some(stage.next)
+}
@@ -0,0 +1,428 @@
+//! Ed25519 (RFC 8032), after TweetNaCl.
+//!
+//! The field elements of GF(2^255 - 19) are 16 limbs of 16 bits, and the
+//! points are in the extended coordinates (X, Y, Z, T).
+
+use crate::sha512::Sha512;
+
+pub const SEED_SIZE: usize = 32;
+pub const PUBLIC_KEY_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+
+type Gf = [i64; 16];
+type Point = [Gf; 4];
+
+const GF0: Gf = [0; 16];
+const GF1: Gf = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+// d = -121665/121666
+const D: Gf = [
+    0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898,
+    0x7779, 0x4079, 0x8cc7, 0xfe73, 0x2b6f, 0x6cee, 0x5203,
+];
+const D2: Gf = [
+    0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130,
+    0xeef3, 0x80f2, 0x198e, 0xfce7, 0x56df, 0xd9dc, 0x2406,
+];
+// The base point.
+const X: Gf = [
+    0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c,
+    0xfdd6, 0xe231, 0xc0a4, 0x53fe, 0xcd6e, 0x36d3, 0x2169,
+];
+const Y: Gf = [
+    0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+    0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+];
+// sqrt(-1)
+const I: Gf = [
+    0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7,
+    0x3dfb, 0x0099, 0x2b4d, 0xdf0b, 0x4fc1, 0x2480, 0x2b83,
+];
+// The order of the base point, little endian.
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2,
+    0xde, 0xf9, 0xde, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+];
+
+fn carry(o: &mut Gf) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+// Swap `p` and `q` if `b` is 1, in constant time.
+fn select(p: &mut Gf, q: &mut Gf, b: i64) {
+    let c = !(b - 1);
+    for i in 0..16 {
+        let t = c & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn pack_gf(n: &Gf) -> [u8; 32] {
+    let mut t = *n;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    for _ in 0..2 {
+        let mut m = GF0;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        select(&mut t, &mut m, 1 - b);
+    }
+    let mut o = [0; 32];
+    for i in 0..16 {
+        o[2 * i] = t[i] as u8;
+        o[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    o
+}
+
+fn unpack_gf(n: &[u8; 32]) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = n[2 * i] as i64 + ((n[2 * i + 1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn neq(a: &Gf, b: &Gf) -> bool {
+    pack_gf(a) != pack_gf(b)
+}
+
+fn parity(a: &Gf) -> u8 {
+    pack_gf(a)[0] & 1
+}
+
+fn add_gf(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] + b[i];
+    }
+    o
+}
+
+fn sub_gf(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] - b[i];
+    }
+    o
+}
+
+fn mul_gf(a: &Gf, b: &Gf) -> Gf {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o = GF0;
+    o.copy_from_slice(&t[..16]);
+    carry(&mut o);
+    carry(&mut o);
+    o
+}
+
+fn square_gf(a: &Gf) -> Gf {
+    mul_gf(a, a)
+}
+
+// a^(p - 2)
+fn invert(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..254).rev() {
+        c = square_gf(&c);
+        if a != 2 && a != 4 {
+            c = mul_gf(&c, i);
+        }
+    }
+    c
+}
+
+// a^((p - 5) / 8)
+fn pow2523(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..251).rev() {
+        c = square_gf(&c);
+        if a != 1 {
+            c = mul_gf(&c, i);
+        }
+    }
+    c
+}
+
+// p += q
+fn add(p: &mut Point, q: &Point) {
+    let a = mul_gf(&sub_gf(&p[1], &p[0]), &sub_gf(&q[1], &q[0]));
+    let b = mul_gf(&add_gf(&p[0], &p[1]), &add_gf(&q[0], &q[1]));
+    let c = mul_gf(&mul_gf(&p[3], &q[3]), &D2);
+    let d = mul_gf(&p[2], &q[2]);
+    let d = add_gf(&d, &d);
+    let e = sub_gf(&b, &a);
+    let f = sub_gf(&d, &c);
+    let g = add_gf(&d, &c);
+    let h = add_gf(&b, &a);
+    p[0] = mul_gf(&e, &f);
+    p[1] = mul_gf(&h, &g);
+    p[2] = mul_gf(&g, &f);
+    p[3] = mul_gf(&e, &h);
+}
+
+fn cswap(p: &mut Point, q: &mut Point, b: u8) {
+    for i in 0..4 {
+        select(&mut p[i], &mut q[i], b as i64);
+    }
+}
+
+fn pack(p: &Point) -> [u8; 32] {
+    let zi = invert(&p[2]);
+    let tx = mul_gf(&p[0], &zi);
+    let ty = mul_gf(&p[1], &zi);
+    let mut r = pack_gf(&ty);
+    r[31] ^= parity(&tx) << 7;
+    r
+}
+
+// s * q
+fn scalarmult(mut q: Point, s: &[u8; 32]) -> Point {
+    let mut p = [GF0, GF1, GF1, GF0];
+    for i in (0..256).rev() {
+        let b = (s[i / 8] >> (i & 7)) & 1;
+        cswap(&mut p, &mut q, b);
+        add(&mut q, &p);
+        let pp = p;
+        add(&mut p, &pp);
+        cswap(&mut p, &mut q, b);
+    }
+    p
+}
+
+// s * B
+fn scalarbase(s: &[u8; 32]) -> Point {
+    scalarmult([X, Y, GF1, mul_gf(&X, &Y)], s)
+}
+
+// The negation of the point that `p` encodes.
+fn unpack_neg(p: &[u8; 32]) -> Option<Point> {
+    let y = unpack_gf(p);
+    let num = square_gf(&y);
+    let den = mul_gf(&num, &D);
+    let num = sub_gf(&num, &GF1);
+    let den = add_gf(&GF1, &den);
+
+    let den2 = square_gf(&den);
+    let den4 = square_gf(&den2);
+    let den6 = mul_gf(&den4, &den2);
+    let mut t = mul_gf(&mul_gf(&den6, &num), &den);
+    t = pow2523(&t);
+    t = mul_gf(&mul_gf(&mul_gf(&t, &num), &den), &den);
+    let mut x = mul_gf(&t, &den);
+
+    if neq(&mul_gf(&square_gf(&x), &den), &num) {
+        x = mul_gf(&x, &I);
+    }
+    if neq(&mul_gf(&square_gf(&x), &den), &num) {
+        return None;
+    }
+    if parity(&x) == p[31] >> 7 {
+        x = sub_gf(&GF0, &x);
+    }
+    Some([x, y, GF1, mul_gf(&x, &y)])
+}
+
+// x mod L
+fn mod_l(x: &mut [i64; 64]) -> [u8; 32] {
+    for i in (32..64).rev() {
+        let mut carry = 0;
+        for j in (i - 32)..(i - 12) {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry << 8;
+        }
+        x[i - 12] += carry;
+        x[i] = 0;
+    }
+    let mut carry = 0;
+    for j in 0..32 {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= carry * L[j];
+    }
+    let mut r = [0; 32];
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        r[i] = x[i] as u8;
+    }
+    r
+}
+
+fn reduce(h: &[u8; 64]) -> [u8; 32] {
+    let mut x = [0i64; 64];
+    for i in 0..64 {
+        x[i] = h[i] as i64;
+    }
+    mod_l(&mut x)
+}
+
+// Whether the scalar `s` is less than L, as RFC 8032 requires of S.
+fn is_canonical(s: &[u8]) -> bool {
+    for i in (0..32).rev() {
+        if (s[i] as i64) != L[i] {
+            return (s[i] as i64) < L[i];
+        }
+    }
+    false
+}
+
+// SHA-512(a || b || c) mod L
+fn hash_reduce(a: &[u8], b: &[u8], c: &[u8]) -> [u8; 32] {
+    let mut sha = Sha512::new();
+    sha.update(a);
+    sha.update(b);
+    sha.update(c);
+    reduce(&sha.finish())
+}
+
+// The secret scalar and the prefix of the nonces.
+fn expand(seed: &[u8; SEED_SIZE]) -> ([u8; 32], [u8; 32]) {
+    let mut sha = Sha512::new();
+    sha.update(seed);
+    let h = sha.finish();
+    let mut a = [0; 32];
+    let mut prefix = [0; 32];
+    a.copy_from_slice(&h[..32]);
+    prefix.copy_from_slice(&h[32..]);
+    a[0] &= 248;
+    a[31] &= 127;
+    a[31] |= 64;
+    (a, prefix)
+}
+
+/// The public key of the secret `seed`.
+pub fn public_key(seed: &[u8; SEED_SIZE]) -> [u8; PUBLIC_KEY_SIZE] {
+    pack(&scalarbase(&expand(seed).0))
+}
+
+/// Sign `msg` with the secret `seed`.
+pub fn sign(seed: &[u8; SEED_SIZE], msg: &[u8]) -> [u8; SIGNATURE_SIZE] {
+    let (a, prefix) = expand(seed);
+    let pk = pack(&scalarbase(&a));
+    let r = hash_reduce(&prefix, msg, &[]);
+    let big_r = pack(&scalarbase(&r));
+    let h = hash_reduce(&big_r, &pk, msg);
+
+    let mut x = [0i64; 64];
+    for i in 0..32 {
+        x[i] = r[i] as i64;
+    }
+    for i in 0..32 {
+        for j in 0..32 {
+            x[i + j] += h[i] as i64 * a[j] as i64;
+        }
+    }
+    let mut sig = [0; SIGNATURE_SIZE];
+    sig[..32].copy_from_slice(&big_r);
+    sig[32..].copy_from_slice(&mod_l(&mut x));
+    sig
+}
+
+/// Check the signature `sig` of `msg` by the public key `pk`.
+pub fn verify(
+    pk: &[u8; PUBLIC_KEY_SIZE],
+    msg: &[u8],
+    sig: &[u8; SIGNATURE_SIZE],
+) -> bool {
+    let q = match unpack_neg(pk) {
+        Some(q) => q,
+        None => return false,
+    };
+    if !is_canonical(&sig[32..]) {
+        return false;
+    }
+    let h = hash_reduce(&sig[..32], pk, msg);
+    let mut s = [0; 32];
+    s.copy_from_slice(&sig[32..]);
+
+    // [S]B - [h]A must be R.
+    let mut p = scalarmult(q, &h);
+    add(&mut p, &scalarbase(&s));
+    pack(&p)[..] == sig[..32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_hex;
+
+    // RFC 8032, section 7.1: (seed, public key, message, signature).
+    const VECTORS: [(&str, &str, &str, &str); 2] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+    ];
+
+    fn vector(i: usize) -> ([u8; 32], [u8; 32], Vec<u8>, [u8; 64]) {
+        let (seed, pk, msg, sig) = VECTORS[i];
+        let mut v = ([0; 32], [0; 32], from_hex(msg), [0; 64]);
+        v.0.copy_from_slice(&from_hex(seed));
+        v.1.copy_from_slice(&from_hex(pk));
+        v.3.copy_from_slice(&from_hex(sig));
+        v
+    }
+
+    #[test]
+    fn known_answers() {
+        for i in 0..VECTORS.len() {
+            let (seed, pk, msg, sig) = vector(i);
+            assert_eq!(public_key(&seed), pk);
+            assert_eq!(sign(&seed, &msg)[..], sig[..]);
+            assert!(verify(&pk, &msg, &sig));
+        }
+    }
+
+    #[test]
+    fn tampered() {
+        let (_, pk, msg, sig) = vector(1);
+        assert!(!verify(&pk, b"\x73", &sig));
+        assert!(!verify(&pk, &[], &sig));
+        for &i in [0, 31, 32, 63].iter() {
+            let mut bad = sig;
+            bad[i] ^= 1;
+            assert!(!verify(&pk, &msg, &bad));
+        }
+        let (_, other, _, _) = vector(0);
+        assert!(!verify(&other, &msg, &sig));
+    }
+}
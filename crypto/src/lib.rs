@@ -0,0 +1,16 @@
+//! The digests and the signatures that the bootloader checks the disk image
+//! with, and that mkimage makes them with.
+#![cfg_attr(not(test), no_std)]
+
+pub mod ed25519;
+pub mod sha256;
+pub mod sha512;
+
+// The bytes written in hex in `s`, for the test vectors.
+#[cfg(test)]
+pub(crate) fn from_hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,578 @@
+use super::fmt::*;
+use super::reloc::RelaIter;
+use super::section::StrTab;
+use super::symbol::SymbolTable;
+use super::{read, ELF};
+use core::mem::size_of;
+
+/// The dynamic segment of an image, as a dynamic loader sees it.
+#[derive(Debug, Clone, Copy)]
+pub struct Dynamic<'a, 'e> {
+    elf: &'e ELF<'a>,
+    data: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+pub struct DynIter<'a> {
+    data: &'a [u8],
+    class: EIClass,
+    cursor: usize,
+}
+
+impl<'a> Iterator for DynIter<'a> {
+    type Item = Dyn64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let off = self.cursor as u64;
+        let result = match self.class {
+            EIClass::Bit32 => read::<Dyn32>(self.data, off).map(From::from),
+            EIClass::Bit64 => read::<Dyn64>(self.data, off),
+        }?;
+        if result.d_tag == DTag::NULL as i64 {
+            self.cursor = self.data.len();
+            return None;
+        }
+        self.cursor += dyn_size(self.class);
+        Some(result)
+    }
+}
+
+const fn dyn_size(class: EIClass) -> usize {
+    match class {
+        EIClass::Bit32 => size_of::<Dyn32>(),
+        EIClass::Bit64 => size_of::<Dyn64>(),
+    }
+}
+
+/// Iterates the words of an address array such as DT_INIT_ARRAY.
+#[derive(Debug, Clone)]
+pub struct AddrIter<'a> {
+    data: &'a [u8],
+    class: EIClass,
+    cursor: usize,
+}
+
+impl<'a> Iterator for AddrIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let off = self.cursor as u64;
+        let (result, size) = match self.class {
+            EIClass::Bit32 => (read::<u32>(self.data, off)? as u64, 4),
+            EIClass::Bit64 => (read::<u64>(self.data, off)?, 8),
+        };
+        self.cursor += size;
+        Some(result)
+    }
+}
+
+// The symbol hash function of DT_HASH.
+fn sysv_hash(name: &[u8]) -> u32 {
+    name.iter().fold(0u32, |h, &c| {
+        let h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf0000000;
+        (h ^ (g >> 24)) & !g
+    })
+}
+
+// The symbol hash function of DT_GNU_HASH.
+fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter()
+        .fold(5381u32, |h, &c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+impl<'a, 'e> Dynamic<'a, 'e> {
+    pub fn iter(&self) -> DynIter<'a> {
+        DynIter {
+            data: self.data,
+            class: self.elf.class,
+            cursor: 0,
+        }
+    }
+
+    /// Value of the first entry tagged `tag`.
+    pub fn get(&self, tag: DTag) -> Option<u64> {
+        self.iter()
+            .find(|dyn_| dyn_.d_tag == tag as i64)
+            .map(|dyn_| dyn_.d_val)
+    }
+
+    pub fn flags(&self) -> DFlags {
+        DFlags::from_bits_truncate(self.get(DTag::FLAGS).unwrap_or(0))
+    }
+
+    /// The string table of DT_STRTAB.
+    pub fn strtab(&self) -> Option<StrTab<'a>> {
+        let addr = self.get(DTag::STRTAB)?;
+        let size = self.get(DTag::STRSZ)?;
+        self.elf.data_at(addr, size).map(StrTab::new)
+    }
+
+    /// Names of the needed libraries (DT_NEEDED).
+    pub fn needed(&self) -> impl Iterator<Item = &'a str> {
+        let strtab = self.strtab();
+        self.iter()
+            .filter(|dyn_| dyn_.d_tag == DTag::NEEDED as i64)
+            .filter_map(move |dyn_| strtab?.get(dyn_.d_val as u32))
+    }
+
+    pub fn soname(&self) -> Option<&'a str> {
+        self.strtab()?.get(self.get(DTag::SONAME)? as u32)
+    }
+
+    /// The symbol table of DT_SYMTAB. Its size is found from the hash
+    /// tables, as the dynamic segment does not record it.
+    pub fn symtab(&self) -> Option<SymbolTable<'a>> {
+        let addr = self.get(DTag::SYMTAB)?;
+        let count = match self.get(DTag::HASH) {
+            // nchain of DT_HASH equals the number of symbols.
+            Some(hash) => self.word(hash, 1)? as u64,
+            None => self.gnu_hash_symbol_count()?,
+        };
+        let size = count.checked_mul(self.sym_size() as u64)?;
+        let data = self.elf.data_at(addr, size)?;
+        let strtab = self.strtab()?;
+        Some(match self.elf.class {
+            EIClass::Bit32 => SymbolTable::new32(data, strtab),
+            EIClass::Bit64 => SymbolTable::new(data, strtab),
+        })
+    }
+
+    fn sym_size(&self) -> usize {
+        match self.elf.class {
+            EIClass::Bit32 => size_of::<Symbol32>(),
+            EIClass::Bit64 => size_of::<Symbol64>(),
+        }
+    }
+
+    // The `index`th 32 bit word at the virtual address `addr`.
+    fn word(&self, addr: u64, index: u64) -> Option<u32> {
+        let addr = index.checked_mul(4)?.checked_add(addr)?;
+        let data = self.elf.data_at(addr, 4)?;
+        read::<u32>(data, 0)
+    }
+
+    /// Relocations of DT_RELA.
+    pub fn relas(&self) -> Option<RelaIter<'a>> {
+        if self.get(DTag::RELAENT)? as usize != size_of::<Rela64>() {
+            return None;
+        }
+        let addr = self.get(DTag::RELA)?;
+        let size = self.get(DTag::RELASZ)?;
+        self.elf.data_at(addr, size).map(RelaIter::new)
+    }
+
+    /// Relocations of the PLT (DT_JMPREL), if they are RELA.
+    pub fn plt_relas(&self) -> Option<RelaIter<'a>> {
+        if self.get(DTag::PLTREL)? != DTag::RELA as u64 {
+            return None;
+        }
+        let addr = self.get(DTag::JMPREL)?;
+        let size = self.get(DTag::PLTRELSZ)?;
+        self.elf.data_at(addr, size).map(RelaIter::new)
+    }
+
+    fn addr_array(&self, addr: DTag, size: DTag) -> Option<AddrIter<'a>> {
+        let data = self.elf.data_at(self.get(addr)?, self.get(size)?)?;
+        Some(AddrIter {
+            data,
+            class: self.elf.class,
+            cursor: 0,
+        })
+    }
+
+    pub fn preinit_array(&self) -> Option<AddrIter<'a>> {
+        self.addr_array(DTag::PREINITARRAY, DTag::PREINITARRAYSZ)
+    }
+
+    pub fn init_array(&self) -> Option<AddrIter<'a>> {
+        self.addr_array(DTag::INITARRAY, DTag::INITARRAYSZ)
+    }
+
+    pub fn fini_array(&self) -> Option<AddrIter<'a>> {
+        self.addr_array(DTag::FINIARRAY, DTag::FINIARRAYSZ)
+    }
+
+    /// Look a defined symbol up by `name` through DT_GNU_HASH, or DT_HASH
+    /// if the former is absent.
+    pub fn lookup(&self, name: &str) -> Option<Symbol64> {
+        let symtab = self.symtab()?;
+        let found = match self.get(DTag::GNUHASH) {
+            Some(hash) => self.gnu_hash_lookup(hash, &symtab, name),
+            None => self.sysv_hash_lookup(self.get(DTag::HASH)?, &symtab, name),
+        }?;
+        if found.st_shndx == crate::section::SHN_UNDEF {
+            return None;
+        }
+        Some(found)
+    }
+
+    fn sysv_hash_lookup(
+        &self,
+        hash: u64,
+        symtab: &SymbolTable<'a>,
+        name: &str,
+    ) -> Option<Symbol64> {
+        let nbucket = self.word(hash, 0)?;
+        let nchain = self.word(hash, 1)?;
+        if nbucket == 0 {
+            return None;
+        }
+        let h = sysv_hash(name.as_bytes());
+        let mut i = self.word(hash, 2 + (h % nbucket) as u64)?;
+        // Chains longer than nchain are loops.
+        for _ in 0..nchain {
+            if i == 0 {
+                break;
+            }
+            let sym = symtab.get(i as usize)?;
+            if symtab.name(&sym) == Some(name) {
+                return Some(sym);
+            }
+            i = self.word(hash, 2 + nbucket as u64 + i as u64)?;
+        }
+        None
+    }
+
+    // Layout of DT_GNU_HASH: (nbuckets, symoffset, bloom_size, bloom_shift)
+    // followed by the bloom filter words, the buckets and the chains.
+    fn gnu_hash_header(&self, hash: u64) -> Option<(u32, u32, u32, u32)> {
+        let bloom_shift = self.word(hash, 3)?;
+        // The hashes are 32 bits wide.
+        if bloom_shift >= 32 {
+            return None;
+        }
+        Some((
+            self.word(hash, 0)?,
+            self.word(hash, 1)?,
+            self.word(hash, 2)?,
+            bloom_shift,
+        ))
+    }
+
+    // The addresses of the bloom filter, the buckets and the chains of the
+    // DT_GNU_HASH at `hash`.
+    fn gnu_hash_tables(
+        &self,
+        hash: u64,
+        nbuckets: u32,
+        bloom_size: u32,
+    ) -> Option<(u64, u64, u64)> {
+        let bloom = hash.checked_add(16)?;
+        let bloom_word = self.bloom_bits() as u64 / 8;
+        let buckets = bloom.checked_add(bloom_size as u64 * bloom_word)?;
+        let chains = buckets.checked_add(nbuckets as u64 * 4)?;
+        Some((bloom, buckets, chains))
+    }
+
+    fn gnu_hash_lookup(
+        &self,
+        hash: u64,
+        symtab: &SymbolTable<'a>,
+        name: &str,
+    ) -> Option<Symbol64> {
+        let (nbuckets, symoffset, bloom_size, bloom_shift) =
+            self.gnu_hash_header(hash)?;
+        if nbuckets == 0 || bloom_size == 0 {
+            return None;
+        }
+        let h1 = gnu_hash(name.as_bytes());
+
+        // Check the bloom filter first.
+        let bits = self.bloom_bits();
+        let (bloom, buckets, chains) =
+            self.gnu_hash_tables(hash, nbuckets, bloom_size)?;
+        let index = ((h1 / bits) % bloom_size) as u64;
+        let word = match self.elf.class {
+            EIClass::Bit32 => self.word(bloom, index)? as u64,
+            EIClass::Bit64 => {
+                let addr = bloom.checked_add(index * 8)?;
+                let data = self.elf.data_at(addr, 8)?;
+                read::<u64>(data, 0)?
+            }
+        };
+        let mask =
+            (1u64 << (h1 % bits)) | (1u64 << ((h1 >> bloom_shift) % bits));
+        if word & mask != mask {
+            return None;
+        }
+
+        let mut i = self.word(buckets, (h1 % nbuckets) as u64)?;
+        if i < symoffset {
+            return None;
+        }
+        loop {
+            let h2 = self.word(chains, (i - symoffset) as u64)?;
+            if h1 | 1 == h2 | 1 {
+                let sym = symtab.get(i as usize)?;
+                if symtab.name(&sym) == Some(name) {
+                    return Some(sym);
+                }
+            }
+            // The lowest bit marks the end of the chain.
+            if h2 & 1 != 0 {
+                return None;
+            }
+            i = i.checked_add(1)?;
+        }
+    }
+
+    // The bloom filter words are as wide as the addresses of the class.
+    fn bloom_bits(&self) -> u32 {
+        match self.elf.class {
+            EIClass::Bit32 => 32,
+            EIClass::Bit64 => 64,
+        }
+    }
+
+    // DT_GNU_HASH does not record the number of symbols, so find it from
+    // the end of the last chain.
+    fn gnu_hash_symbol_count(&self) -> Option<u64> {
+        let hash = self.get(DTag::GNUHASH)?;
+        let (nbuckets, symoffset, bloom_size, _) =
+            self.gnu_hash_header(hash)?;
+        let (_, buckets, chains) =
+            self.gnu_hash_tables(hash, nbuckets, bloom_size)?;
+        let mut last = 0;
+        for i in 0..nbuckets as u64 {
+            last = last.max(self.word(buckets, i)?);
+        }
+        if last < symoffset {
+            return Some(symoffset as u64);
+        }
+        while self.word(chains, (last - symoffset) as u64)? & 1 == 0 {
+            last = last.checked_add(1)?;
+        }
+        Some(last as u64 + 1)
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// The dynamic segment (PT_DYNAMIC).
+    pub fn dynamic(&self) -> Option<Dynamic<'a, '_>> {
+        let phdr = self
+            .phdrs()
+            .find(|phdr| phdr.kind() == Some(PType::DYNAMIC))?;
+        let start = phdr.p_offset as usize;
+        let data = self
+            .inp
+            .get(start..start.checked_add(phdr.p_filesz as usize)?)?;
+        Some(Dynamic { elf: self, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes_of;
+    use crate::testutil::image;
+
+    // Where the tables are, in a LOAD segment at 0x1000.
+    const TABLES: u64 = 0x1000;
+    const STRTAB: usize = 0;
+    const SYMTAB: usize = 0x100;
+    const HASH: usize = 0x200;
+    const NAMES: &[u8] = b"\0libc.so\0foo\0bar\0baz\0";
+    // foo and bar are defined, and baz is not.
+    const SYMBOLS: [(u32, u16); 4] = [(0, 0), (9, 1), (13, 1), (17, 0)];
+
+    fn put32(tables: &mut [u8], off: usize, v: u32) {
+        tables[off..off + 4].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn name(sym: usize) -> &'static [u8] {
+        let start = SYMBOLS[sym].0 as usize;
+        let len = NAMES[start..].iter().position(|&c| c == 0).unwrap();
+        &NAMES[start..start + len]
+    }
+
+    // DT_HASH of 2 buckets for the symbols.
+    fn sysv_table(tables: &mut [u8]) {
+        let (nbucket, nchain) = (2, SYMBOLS.len());
+        put32(tables, HASH, nbucket as u32);
+        put32(tables, HASH + 4, nchain as u32);
+        let chains = HASH + 8 + nbucket * 4;
+        for i in 1..nchain {
+            let bucket = HASH + 8 + sysv_hash(name(i)) as usize % nbucket * 4;
+            let next = u32::from_le_bytes([
+                tables[bucket],
+                tables[bucket + 1],
+                tables[bucket + 2],
+                tables[bucket + 3],
+            ]);
+            put32(tables, chains + i * 4, next);
+            put32(tables, bucket, i as u32);
+        }
+    }
+
+    // DT_GNU_HASH of a bucket and a bloom word, with a shift of 6.
+    fn gnu_table(tables: &mut [u8]) {
+        put32(tables, HASH, 1);
+        put32(tables, HASH + 4, 1);
+        put32(tables, HASH + 8, 1);
+        put32(tables, HASH + 12, 6);
+        let mut bloom = 0u64;
+        for i in 1..SYMBOLS.len() {
+            let h = gnu_hash(name(i));
+            bloom |= 1 << (h % 64) | 1 << ((h >> 6) % 64);
+            let end = (i == SYMBOLS.len() - 1) as u32;
+            put32(tables, HASH + 28 + (i - 1) * 4, h & !1 | end);
+        }
+        tables[HASH + 16..HASH + 24].copy_from_slice(&bloom.to_le_bytes());
+        put32(tables, HASH + 24, 1);
+    }
+
+    // A shared object of the symbols, hashed with DT_GNU_HASH if `gnu` and
+    // with DT_HASH otherwise, after `corrupt` breaks the tables.
+    fn shared_object<F>(gnu: bool, corrupt: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let mut tables = [0; 0x300];
+        tables[STRTAB..NAMES.len()].copy_from_slice(NAMES);
+        for (i, &(name, shndx)) in SYMBOLS.iter().enumerate() {
+            let sym = Symbol64 {
+                st_name: name,
+                st_info: 1 << 4 | STType::FUNC as u8,
+                st_other: 0,
+                st_shndx: shndx,
+                st_value: if shndx == 0 { 0 } else { 0x1800 + i as u64 },
+                st_size: 1,
+            };
+            let off = SYMTAB + i * size_of::<Symbol64>();
+            tables[off..off + size_of::<Symbol64>()]
+                .copy_from_slice(bytes_of(&sym));
+        }
+        if gnu {
+            gnu_table(&mut tables);
+        } else {
+            sysv_table(&mut tables);
+        }
+        corrupt(&mut tables);
+
+        let hash = if gnu { DTag::GNUHASH } else { DTag::HASH };
+        let dynamic = [
+            (DTag::NEEDED, 1),
+            (DTag::STRTAB, TABLES + STRTAB as u64),
+            (DTag::STRSZ, NAMES.len() as u64),
+            (DTag::SYMTAB, TABLES + SYMTAB as u64),
+            (DTag::SYMENT, size_of::<Symbol64>() as u64),
+            (hash, TABLES + HASH as u64),
+            (DTag::NULL, 0),
+        ]
+        .iter()
+        .flat_map(|&(tag, val)| {
+            bytes_of(&Dyn64 {
+                d_tag: tag as i64,
+                d_val: val,
+            })
+            .to_vec()
+        })
+        .collect::<Vec<_>>();
+
+        let mut buf = image(EType::DYN, |writer| {
+            writer.load(TABLES, PFlags::R, &tables, 0).unwrap();
+            writer.load(0x2000, PFlags::R, &dynamic, 0).unwrap();
+        });
+        // Turn the second segment into the dynamic one.
+        let phdr = size_of::<ELFHeader64>() + size_of::<ProgHeader64>();
+        put32(&mut buf, phdr, PType::DYNAMIC as u32);
+        buf
+    }
+
+    fn lookup(buf: &[u8], name: &str) -> Option<u64> {
+        let elf = ELF::parse(buf).unwrap();
+        let sym = elf.dynamic().unwrap().lookup(name);
+        sym.map(|sym| sym.st_value)
+    }
+
+    #[test]
+    fn tables() {
+        for &gnu in [false, true].iter() {
+            let buf = shared_object(gnu, |_| ());
+            let elf = ELF::parse(&buf).unwrap();
+            let dynamic = elf.dynamic().unwrap();
+            assert_eq!(dynamic.needed().collect::<Vec<_>>(), ["libc.so"]);
+            assert_eq!(dynamic.symtab().map(|symtab| symtab.len()), Some(4));
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        for &gnu in [false, true].iter() {
+            let buf = shared_object(gnu, |_| ());
+            assert_eq!(lookup(&buf, "foo"), Some(0x1801));
+            assert_eq!(lookup(&buf, "bar"), Some(0x1802));
+            // Undefined.
+            assert_eq!(lookup(&buf, "baz"), None);
+            assert_eq!(lookup(&buf, "qux"), None);
+            assert_eq!(lookup(&buf, ""), None);
+        }
+    }
+
+    #[test]
+    fn bloom_filter() {
+        // An empty filter turns every name down.
+        let buf = shared_object(true, |tables| {
+            tables[HASH + 16..HASH + 24].copy_from_slice(&[0; 8]);
+        });
+        assert_eq!(lookup(&buf, "foo"), None);
+        let buf = shared_object(true, |tables| put32(tables, HASH + 12, 32));
+        assert_eq!(lookup(&buf, "foo"), None);
+    }
+
+    #[test]
+    fn corrupt_sysv_hash() {
+        let corruptions: [fn(&mut [u8]); 4] = [
+            // No buckets.
+            |tables| put32(tables, HASH, 0),
+            // Buckets past the end of the tables.
+            |tables| put32(tables, HASH, u32::MAX),
+            // A bucket past the end of the symbols.
+            |tables| {
+                put32(tables, HASH + 8, 100);
+                put32(tables, HASH + 12, 100);
+            },
+            // A chain past the end of the tables.
+            |tables| {
+                put32(tables, HASH + 4, u32::MAX);
+                for off in (HASH + 16..HASH + 32).step_by(4) {
+                    put32(tables, off, u32::MAX >> 2);
+                }
+            },
+        ];
+        for corrupt in corruptions.iter() {
+            let buf = shared_object(false, corrupt);
+            assert_eq!(lookup(&buf, "foo"), None);
+            assert_eq!(lookup(&buf, "bar"), None);
+        }
+    }
+
+    #[test]
+    fn corrupt_gnu_hash() {
+        let corruptions: [fn(&mut [u8]); 5] = [
+            // No buckets.
+            |tables| put32(tables, HASH, 0),
+            // A bloom filter past the end of the tables.
+            |tables| put32(tables, HASH + 8, u32::MAX),
+            // A bucket below symoffset.
+            |tables| put32(tables, HASH + 4, 2),
+            // A bucket past the end of the chains.
+            |tables| put32(tables, HASH + 24, u32::MAX),
+            // A chain that runs off the end of the tables.
+            |tables| {
+                for off in (HASH + 28..HASH + 0x100).step_by(4) {
+                    put32(tables, off, 0);
+                }
+            },
+        ];
+        for corrupt in corruptions.iter() {
+            let buf = shared_object(true, corrupt);
+            assert_eq!(lookup(&buf, "foo"), None);
+        }
+        // The symbol count is unknown without the end of a chain.
+        let buf = shared_object(true, corruptions[4]);
+        let elf = ELF::parse(&buf).unwrap();
+        assert!(elf.dynamic().unwrap().symtab().is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use core::fmt;
+
+/// Reasons for rejecting an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input is smaller than the ELF header.
+    TooShort,
+    /// The input does not start with "\x7FELF".
+    BadMagic,
+    /// EI_CLASS is not supported.
+    UnsupportedClass(u8),
+    /// EI_DATA is not supported.
+    UnsupportedEncoding(u8),
+    /// EI_VERSION or e_version is not the current version.
+    UnsupportedVersion(u32),
+    /// e_machine is not supported.
+    UnsupportedMachine(u16),
+    /// e_ehsize does not match the size of the ELF header.
+    BadHeaderSize(u16),
+    /// e_phentsize does not match the size of a program header.
+    BadPhdrSize(u16),
+    /// e_shentsize does not match the size of a section header.
+    BadShdrSize(u16),
+    /// The program header table lies outside of the input.
+    PhdrsOutOfBounds,
+    /// The section header table lies outside of the input.
+    ShdrsOutOfBounds,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooShort => write!(f, "input too short"),
+            ParseError::BadMagic => write!(f, "bad magic"),
+            ParseError::UnsupportedClass(v) => {
+                write!(f, "unsupported class {}", v)
+            }
+            ParseError::UnsupportedEncoding(v) => {
+                write!(f, "unsupported data encoding {}", v)
+            }
+            ParseError::UnsupportedVersion(v) => {
+                write!(f, "unsupported version {}", v)
+            }
+            ParseError::UnsupportedMachine(v) => {
+                write!(f, "unsupported machine 0x{:x}", v)
+            }
+            ParseError::BadHeaderSize(v) => {
+                write!(f, "bad ELF header size {}", v)
+            }
+            ParseError::BadPhdrSize(v) => {
+                write!(f, "bad program header size {}", v)
+            }
+            ParseError::BadShdrSize(v) => {
+                write!(f, "bad section header size {}", v)
+            }
+            ParseError::PhdrsOutOfBounds => {
+                write!(f, "program headers out of bounds")
+            }
+            ParseError::ShdrsOutOfBounds => {
+                write!(f, "section headers out of bounds")
+            }
+        }
+    }
+}
+
+/// Reasons for failing to apply a relocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocError {
+    /// The relocation type is not supported.
+    Unsupported(u32),
+    /// The resolver could not resolve the symbol with this index.
+    UndefinedSymbol(u32),
+    /// The relocation targets an address outside of the image.
+    OutOfBounds(u64),
+    /// The value does not fit in the relocated field at this address.
+    Overflow(u64),
+    /// The GOT slot that the field at this address points to does not hold
+    /// the symbol.
+    BadGotSlot(u64),
+}
+
+impl fmt::Display for RelocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelocError::Unsupported(v) => {
+                write!(f, "unsupported relocation type {}", v)
+            }
+            RelocError::UndefinedSymbol(v) => {
+                write!(f, "undefined symbol {}", v)
+            }
+            RelocError::OutOfBounds(v) => {
+                write!(f, "relocation at 0x{:x} out of bounds", v)
+            }
+            RelocError::Overflow(v) => {
+                write!(f, "relocation at 0x{:x} overflows", v)
+            }
+            RelocError::BadGotSlot(v) => {
+                write!(f, "relocation at 0x{:x} loads a foreign GOT slot", v)
+            }
+        }
+    }
+}
+
+/// Reasons for failing to write an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteError {
+    /// More segments were added than the program header table was sized
+    /// for.
+    TooManySegments,
+    /// The output cannot hold the image up to this offset.
+    NoSpace(u64),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::TooManySegments => write!(f, "too many segments"),
+            WriteError::NoSpace(v) => {
+                write!(f, "no space for the image up to 0x{:x}", v)
+            }
+        }
+    }
+}
+
+/// Reasons for rejecting the segment layout of an ELF image. The segments
+/// are numbered by their index in the program header table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutError {
+    /// p_filesz of the segment exceeds its p_memsz.
+    FileSizeExceedsMemSize(u16),
+    /// p_align of the segment is not a power of two.
+    BadAlign(u16),
+    /// p_vaddr and p_offset of the segment are not congruent modulo p_align.
+    Misaligned(u16),
+    /// The segment lies outside of the allowed window.
+    OutOfWindow(u16),
+    /// The two segments overlap.
+    Overlap(u16, u16),
+    /// The entry point is not inside of an executable segment.
+    BadEntry(u64),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::FileSizeExceedsMemSize(v) => {
+                write!(f, "segment {}: file size exceeds memory size", v)
+            }
+            LayoutError::BadAlign(v) => {
+                write!(f, "segment {}: alignment is not a power of two", v)
+            }
+            LayoutError::Misaligned(v) => {
+                write!(f, "segment {}: address and offset are misaligned", v)
+            }
+            LayoutError::OutOfWindow(v) => {
+                write!(f, "segment {}: out of the allowed window", v)
+            }
+            LayoutError::Overlap(a, b) => {
+                write!(f, "segments {} and {} overlap", a, b)
+            }
+            LayoutError::BadEntry(v) => {
+                write!(f, "entry point 0x{:x} is not executable", v)
+            }
+        }
+    }
+}
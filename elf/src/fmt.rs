@@ -1,4 +1,24 @@
 use bitflags::bitflags;
+use core::convert::TryFrom;
+
+// Implement TryFrom of the representation for an enum with the listed
+// variants. The conversion fails with the value that matches no variant.
+macro_rules! impl_try_from {
+    ($name:ident: $repr:ty { $($variant:ident),* $(,)? }) => {
+        impl TryFrom<$repr> for $name {
+            type Error = $repr;
+
+            fn try_from(v: $repr) -> Result<Self, Self::Error> {
+                $(
+                    if v == $name::$variant as $repr {
+                        return Ok($name::$variant);
+                    }
+                )*
+                Err(v)
+            }
+        }
+    };
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -7,6 +27,10 @@ pub enum EIClass {
     Bit64 = 2,
 }
 
+impl_try_from!(EIClass: u8 {
+    Bit32, Bit64,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum EIData {
@@ -14,6 +38,10 @@ pub enum EIData {
     BEndian = 2,
 }
 
+impl_try_from!(EIData: u8 {
+    LEndian, BEndian,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum OSabi {
@@ -36,6 +64,11 @@ pub enum OSabi {
     CloudABI = 17,
 }
 
+impl_try_from!(OSabi: u8 {
+    SystemV, HPUX, NetBsd, Linux, GnuHurd, Solaris, AIX, IRIX, FreeBsd, Tru64,
+    NovellModesto, OpenBSD, OpenVMS, NonStopKernel, AROS, FenixOS, CloudABI,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum EType {
@@ -50,6 +83,10 @@ pub enum EType {
     HIPROC = 0xffff,
 }
 
+impl_try_from!(EType: u16 {
+    NONE, REL, EXEC, DYN, CORE, LOOS, HIOS, LOPROC, HIPROC,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum EMachine {
@@ -66,6 +103,10 @@ pub enum EMachine {
     RISCV = 0xF3,
 }
 
+impl_try_from!(EMachine: u16 {
+    SPARC, X86, MIPS, PowerPC, S390, ARM, SuperH, IA64, X8664, AArch64, RISCV,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum PType {
@@ -76,12 +117,18 @@ pub enum PType {
     NOTE = 0x4,
     SHLIB = 0x5,
     PHDR = 0x6,
+    TLS = 0x7,
     LOOS = 0x60000000,
     HIOS = 0x6FFFFFFF,
     LOPROC = 0x70000000,
     HIPROC = 0x7FFFFFFF,
 }
 
+impl_try_from!(PType: u32 {
+    NULL, LOAD, DYNAMIC, INTERP, NOTE, SHLIB, PHDR, TLS, LOOS, HIOS, LOPROC,
+    HIPROC,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum SHType {
@@ -104,8 +151,146 @@ pub enum SHType {
     SYMTABSHNDX = 0x12,
     NUM = 0x13,
     LOOS = 0x60000000,
+    GNUHASH = 0x6ffffff6,
+    GNUVERDEF = 0x6ffffffd,
+    GNUVERNEED = 0x6ffffffe,
+    GNUVERSYM = 0x6fffffff,
 }
 
+impl_try_from!(SHType: u32 {
+    NULL, PROGBITS, SYMTAB, STRTAB, RELA, HASH, DYNAMIC, NOTE, NOBITS, REL,
+    SHLIB, DYNSYM, INITARRAY, FINIARRAY, PREINITARRAY, GROUP, SYMTABSHNDX, NUM,
+    LOOS, GNUHASH, GNUVERDEF, GNUVERNEED, GNUVERSYM,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i64)]
+pub enum DTag {
+    NULL = 0,
+    NEEDED = 1,
+    PLTRELSZ = 2,
+    PLTGOT = 3,
+    HASH = 4,
+    STRTAB = 5,
+    SYMTAB = 6,
+    RELA = 7,
+    RELASZ = 8,
+    RELAENT = 9,
+    STRSZ = 10,
+    SYMENT = 11,
+    INIT = 12,
+    FINI = 13,
+    SONAME = 14,
+    RPATH = 15,
+    SYMBOLIC = 16,
+    REL = 17,
+    RELSZ = 18,
+    RELENT = 19,
+    PLTREL = 20,
+    DEBUG = 21,
+    TEXTREL = 22,
+    JMPREL = 23,
+    BINDNOW = 24,
+    INITARRAY = 25,
+    FINIARRAY = 26,
+    INITARRAYSZ = 27,
+    FINIARRAYSZ = 28,
+    RUNPATH = 29,
+    FLAGS = 30,
+    PREINITARRAY = 32,
+    PREINITARRAYSZ = 33,
+    GNUHASH = 0x6ffffef5,
+    VERSYM = 0x6ffffff0,
+    RELACOUNT = 0x6ffffff9,
+    RELCOUNT = 0x6ffffffa,
+    FLAGS1 = 0x6ffffffb,
+    VERDEF = 0x6ffffffc,
+    VERDEFNUM = 0x6ffffffd,
+    VERNEED = 0x6ffffffe,
+    VERNEEDNUM = 0x6fffffff,
+}
+
+impl_try_from!(DTag: i64 {
+    NULL, NEEDED, PLTRELSZ, PLTGOT, HASH, STRTAB, SYMTAB, RELA, RELASZ,
+    RELAENT, STRSZ, SYMENT, INIT, FINI, SONAME, RPATH, SYMBOLIC, REL, RELSZ,
+    RELENT, PLTREL, DEBUG, TEXTREL, JMPREL, BINDNOW, INITARRAY, FINIARRAY,
+    INITARRAYSZ, FINIARRAYSZ, RUNPATH, FLAGS, PREINITARRAY, PREINITARRAYSZ,
+    GNUHASH, VERSYM, RELACOUNT, RELCOUNT, FLAGS1, VERDEF, VERDEFNUM, VERNEED,
+    VERNEEDNUM,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum STBind {
+    LOCAL = 0,
+    GLOBAL = 1,
+    WEAK = 2,
+    LOOS = 10,
+    HIOS = 12,
+    LOPROC = 13,
+    HIPROC = 15,
+}
+
+impl_try_from!(STBind: u8 {
+    LOCAL, GLOBAL, WEAK, LOOS, HIOS, LOPROC, HIPROC,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum STType {
+    NOTYPE = 0,
+    OBJECT = 1,
+    FUNC = 2,
+    SECTION = 3,
+    FILE = 4,
+    COMMON = 5,
+    TLS = 6,
+    LOOS = 10,
+    HIOS = 12,
+    LOPROC = 13,
+    HIPROC = 15,
+}
+
+impl_try_from!(STType: u8 {
+    NOTYPE, OBJECT, FUNC, SECTION, FILE, COMMON, TLS, LOOS, HIOS, LOPROC,
+    HIPROC,
+});
+
+// Relocation types of x86_64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum RelocX8664 {
+    NONE = 0,
+    R64 = 1,
+    PC32 = 2,
+    GOT32 = 3,
+    PLT32 = 4,
+    COPY = 5,
+    GLOBDAT = 6,
+    JUMPSLOT = 7,
+    RELATIVE = 8,
+    GOTPCREL = 9,
+    R32 = 10,
+    R32S = 11,
+    R16 = 12,
+    PC16 = 13,
+    R8 = 14,
+    PC8 = 15,
+    DTPMOD64 = 16,
+    DTPOFF64 = 17,
+    TPOFF64 = 18,
+    PC64 = 24,
+    IRELATIVE = 37,
+    GOTPCRELX = 41,
+    REXGOTPCRELX = 42,
+}
+
+impl_try_from!(RelocX8664: u32 {
+    NONE, R64, PC32, GOT32, PLT32, COPY, GLOBDAT, JUMPSLOT, RELATIVE, GOTPCREL,
+    R32, R32S, R16, PC16, R8, PC8, DTPMOD64, DTPOFF64, TPOFF64, PC64, IRELATIVE,
+    GOTPCRELX, REXGOTPCRELX,
+});
+
 bitflags! {
     pub struct SHFlags64: u64 {
         const WRITE            = 0x1;
@@ -125,6 +310,26 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct PFlags: u32 {
+        const X        = 0x1;
+        const W        = 0x2;
+        const R        = 0x4;
+        const MASKOS   = 0x0ff00000;
+        const MASKPROC = 0xf0000000;
+    }
+}
+
+bitflags! {
+    pub struct DFlags: u64 {
+        const ORIGIN     = 0x1;
+        const SYMBOLIC   = 0x2;
+        const TEXTREL    = 0x4;
+        const BIND_NOW   = 0x8;
+        const STATIC_TLS = 0x10;
+    }
+}
+
 // The 64 bit ELF Header.
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -206,6 +411,36 @@ pub struct ProgHeader64 {
     pub p_align: u64,
 }
 
+impl ELFHeader64 {
+    /// Type of this object file, or None if it is not known.
+    pub fn kind(&self) -> Option<EType> {
+        EType::try_from(self.e_type).ok()
+    }
+
+    pub fn machine(&self) -> Option<EMachine> {
+        EMachine::try_from(self.e_machine).ok()
+    }
+
+    pub fn osabi(&self) -> Option<OSabi> {
+        OSabi::try_from(self.ei_osabi).ok()
+    }
+}
+
+impl ProgHeader64 {
+    /// Type of this segment, or None if it is not known.
+    pub fn kind(&self) -> Option<PType> {
+        PType::try_from(self.p_type).ok()
+    }
+
+    pub const fn flags(&self) -> PFlags {
+        PFlags::from_bits_truncate(self.p_flags)
+    }
+
+    pub fn is_load(&self) -> bool {
+        self.kind() == Some(PType::LOAD)
+    }
+}
+
 // The 64 bit section header.
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -236,3 +471,263 @@ pub struct SectHeader64 {
     // fixed-size entries. Otherwise, this field contains zero.
     pub sh_entsize: u64,
 }
+
+impl SectHeader64 {
+    /// Type of this section, or None if it is not known.
+    pub fn kind(&self) -> Option<SHType> {
+        SHType::try_from(self.sh_type).ok()
+    }
+
+    pub const fn flags(&self) -> SHFlags64 {
+        SHFlags64::from_bits_truncate(self.sh_flags)
+    }
+}
+
+// The 64 bit symbol table entry.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Symbol64 {
+    // An offset to a string in the associated string table that represents
+    // the name of this symbol. Zero if the symbol has no name.
+    pub st_name: u32,
+    // Binding (high nibble) and type (low nibble) of the symbol.
+    pub st_info: u8,
+    // Visibility of the symbol in the low two bits.
+    pub st_other: u8,
+    // Index of the section this symbol is defined in relation to.
+    pub st_shndx: u16,
+    // Value of the symbol; for executables, its virtual address.
+    pub st_value: u64,
+    // Size of the object this symbol refers to. May be 0.
+    pub st_size: u64,
+}
+
+impl Symbol64 {
+    pub fn bind(&self) -> Option<STBind> {
+        STBind::try_from(self.st_info >> 4).ok()
+    }
+
+    pub fn kind(&self) -> Option<STType> {
+        STType::try_from(self.st_info & 0xf).ok()
+    }
+
+    pub fn is_function(&self) -> bool {
+        self.kind() == Some(STType::FUNC)
+    }
+}
+
+// The 64 bit relocation entry with an explicit addend.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Rela64 {
+    // Location to apply the relocation, as a virtual address for
+    // executables and shared objects, or a section offset for
+    // relocatable objects.
+    pub r_offset: u64,
+    // Symbol index (high 32 bits) and relocation type (low 32 bits).
+    pub r_info: u64,
+    // Constant addend used to compute the value to be stored.
+    pub r_addend: i64,
+}
+
+impl Rela64 {
+    /// Index of the symbol in the linked symbol table.
+    pub const fn sym(&self) -> u32 {
+        (self.r_info >> 32) as u32
+    }
+
+    pub const fn rtype(&self) -> u32 {
+        self.r_info as u32
+    }
+}
+
+// The 64 bit dynamic section entry.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Dyn64 {
+    // Identifies the type of this entry. DT_NULL ends the dynamic section.
+    pub d_tag: i64,
+    // An integer or a virtual address, depending on d_tag.
+    pub d_val: u64,
+}
+
+impl Dyn64 {
+    /// Tag of this entry, or None if it is not known.
+    pub fn tag(&self) -> Option<DTag> {
+        DTag::try_from(self.d_tag).ok()
+    }
+}
+
+// The 32 bit ELF Header.
+// Fields are as in ELFHeader64, but addresses and offsets are 32 bits long.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct ELFHeader32 {
+    pub ei_magic: u32,
+    pub ei_class: u8,
+    pub ei_data: u8,
+    pub ei_version: u8,
+    pub ei_osabi: u8,
+    pub ei_abiversion: u8,
+    pub ei_pad: [u8; 7],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u32,
+    pub e_phoff: u32,
+    pub e_shoff: u32,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+// The 32 bit program header.
+// Note that p_flags comes after p_memsz, unlike ProgHeader64.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct ProgHeader32 {
+    pub p_type: u32,
+    pub p_offset: u32,
+    pub p_vaddr: u32,
+    pub p_paddr: u32,
+    pub p_filesz: u32,
+    pub p_memsz: u32,
+    pub p_flags: u32,
+    pub p_align: u32,
+}
+
+// The 32 bit section header.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct SectHeader32 {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u32,
+    pub sh_addr: u32,
+    pub sh_offset: u32,
+    pub sh_size: u32,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u32,
+    pub sh_entsize: u32,
+}
+
+// The 32 bit symbol table entry.
+// Note that st_value and st_size come before st_info, unlike Symbol64.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Symbol32 {
+    pub st_name: u32,
+    pub st_value: u32,
+    pub st_size: u32,
+    pub st_info: u8,
+    pub st_other: u8,
+    pub st_shndx: u16,
+}
+
+// The 32 bit dynamic section entry.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Dyn32 {
+    pub d_tag: i32,
+    pub d_val: u32,
+}
+
+impl From<ELFHeader32> for ELFHeader64 {
+    fn from(hdr: ELFHeader32) -> Self {
+        ELFHeader64 {
+            ei_magic: hdr.ei_magic,
+            ei_class: hdr.ei_class,
+            ei_data: hdr.ei_data,
+            ei_version: hdr.ei_version,
+            ei_osabi: hdr.ei_osabi,
+            ei_abiversion: hdr.ei_abiversion,
+            ei_pad: hdr.ei_pad,
+            e_type: hdr.e_type,
+            e_machine: hdr.e_machine,
+            e_version: hdr.e_version,
+            e_entry: hdr.e_entry as u64,
+            e_phoff: hdr.e_phoff as u64,
+            e_shoff: hdr.e_shoff as u64,
+            e_flags: hdr.e_flags,
+            e_ehsize: hdr.e_ehsize,
+            e_phentsize: hdr.e_phentsize,
+            e_phnum: hdr.e_phnum,
+            e_shentsize: hdr.e_shentsize,
+            e_shnum: hdr.e_shnum,
+            e_shstrndx: hdr.e_shstrndx,
+        }
+    }
+}
+
+impl From<ProgHeader32> for ProgHeader64 {
+    fn from(phdr: ProgHeader32) -> Self {
+        ProgHeader64 {
+            p_type: phdr.p_type,
+            p_flags: phdr.p_flags,
+            p_offset: phdr.p_offset as u64,
+            p_vaddr: phdr.p_vaddr as u64,
+            p_paddr: phdr.p_paddr as u64,
+            p_filesz: phdr.p_filesz as u64,
+            p_memsz: phdr.p_memsz as u64,
+            p_align: phdr.p_align as u64,
+        }
+    }
+}
+
+impl From<SectHeader32> for SectHeader64 {
+    fn from(shdr: SectHeader32) -> Self {
+        SectHeader64 {
+            sh_name: shdr.sh_name,
+            sh_type: shdr.sh_type,
+            sh_flags: shdr.sh_flags as u64,
+            sh_addr: shdr.sh_addr as u64,
+            sh_offset: shdr.sh_offset as u64,
+            sh_size: shdr.sh_size as u64,
+            sh_link: shdr.sh_link,
+            sh_info: shdr.sh_info,
+            sh_addralign: shdr.sh_addralign as u64,
+            sh_entsize: shdr.sh_entsize as u64,
+        }
+    }
+}
+
+impl From<Symbol32> for Symbol64 {
+    fn from(sym: Symbol32) -> Self {
+        Symbol64 {
+            st_name: sym.st_name,
+            st_info: sym.st_info,
+            st_other: sym.st_other,
+            st_shndx: sym.st_shndx,
+            st_value: sym.st_value as u64,
+            st_size: sym.st_size as u64,
+        }
+    }
+}
+
+// The header of an entry in a note segment or section.
+// The name and the descriptor follow, each padded to the alignment of the
+// notes.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct NoteHeader {
+    // Size in bytes of the name, including the terminating NUL.
+    pub n_namesz: u32,
+    // Size in bytes of the descriptor.
+    pub n_descsz: u32,
+    // Type of the note, interpreted according to the name.
+    pub n_type: u32,
+}
+
+impl From<Dyn32> for Dyn64 {
+    fn from(dyn_: Dyn32) -> Self {
+        Dyn64 {
+            d_tag: dyn_.d_tag as i64,
+            d_val: dyn_.d_val as u64,
+        }
+    }
+}
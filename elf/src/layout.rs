@@ -0,0 +1,206 @@
+use super::error::LayoutError;
+use super::fmt::*;
+use super::ELF;
+use core::ops::Range;
+
+/// The addresses that the loader places the segments at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrSpace {
+    /// p_vaddr, as loaders of user programs do.
+    Virtual,
+    /// p_paddr, as the bootloader does for the kernel.
+    Physical,
+}
+
+impl AddrSpace {
+    // The address that `phdr` is placed at.
+    fn start(self, phdr: &ProgHeader64) -> u64 {
+        match self {
+            AddrSpace::Virtual => phdr.p_vaddr,
+            AddrSpace::Physical => phdr.p_paddr,
+        }
+    }
+
+    // The range that `phdr` occupies in memory.
+    fn range(self, phdr: &ProgHeader64) -> Option<Range<u64>> {
+        let start = self.start(phdr);
+        Some(start..start.checked_add(phdr.p_memsz)?)
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// Check that the LOAD segments are safe to place at the addresses of
+    /// `space`, before any of them is loaded. Every segment must be well
+    /// formed and lie inside of `window` without overlapping the others, and
+    /// the entry point must be inside of an executable segment.
+    pub fn validate_layout(
+        &self,
+        space: AddrSpace,
+        window: Range<u64>,
+    ) -> Result<(), LayoutError> {
+        let loads = || {
+            self.phdrs()
+                .enumerate()
+                .filter(|(_, phdr)| phdr.is_load())
+                .map(|(i, phdr)| (i as u16, phdr))
+        };
+        for (i, phdr) in loads() {
+            if phdr.p_filesz > phdr.p_memsz {
+                return Err(LayoutError::FileSizeExceedsMemSize(i));
+            }
+            // 0 and 1 mean no alignment.
+            if phdr.p_align > 1 {
+                if !phdr.p_align.is_power_of_two() {
+                    return Err(LayoutError::BadAlign(i));
+                }
+                let mask = phdr.p_align - 1;
+                if space.start(&phdr) & mask != phdr.p_offset & mask {
+                    return Err(LayoutError::Misaligned(i));
+                }
+            }
+            let range =
+                space.range(&phdr).ok_or(LayoutError::OutOfWindow(i))?;
+            if range.start < window.start || range.end > window.end {
+                return Err(LayoutError::OutOfWindow(i));
+            }
+            for (j, other) in loads().take_while(|&(j, _)| j < i) {
+                match space.range(&other) {
+                    Some(other)
+                        if range.start < other.end
+                            && other.start < range.end =>
+                    {
+                        return Err(LayoutError::Overlap(j, i))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let entry = self.entry();
+        loads()
+            .find(|(_, phdr)| {
+                phdr.flags().contains(PFlags::X)
+                    && phdr.p_vaddr <= entry
+                    && entry - phdr.p_vaddr < phdr.p_memsz
+            })
+            .map(|_| ())
+            .ok_or(LayoutError::BadEntry(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes_of;
+    use crate::writer::Writer;
+    use core::mem::size_of;
+
+    fn load(offset: u64, vaddr: u64, filesz: u64, memsz: u64) -> ProgHeader64 {
+        ProgHeader64 {
+            p_type: PType::LOAD as u32,
+            p_flags: (PFlags::R | PFlags::X).bits(),
+            p_offset: offset,
+            p_vaddr: vaddr,
+            p_paddr: vaddr - 0x400000,
+            p_filesz: filesz,
+            p_memsz: memsz,
+            p_align: 0x1000,
+        }
+    }
+
+    // An executable of the segments `phdrs` entered at `entry`.
+    fn image(entry: u64, phdrs: &[ProgHeader64]) -> Vec<u8> {
+        let mut buf = vec![0; 0x2000];
+        let mut writer = Writer::new(&mut buf[..], EType::EXEC, 4);
+        writer.set_entry(entry);
+        for _ in phdrs {
+            writer.load(0, PFlags::R, &[], 0).unwrap();
+        }
+        writer.finish().unwrap();
+        for (i, phdr) in phdrs.iter().enumerate() {
+            let off = size_of::<ELFHeader64>() + i * size_of::<ProgHeader64>();
+            buf[off..off + size_of::<ProgHeader64>()]
+                .copy_from_slice(bytes_of(phdr));
+        }
+        buf
+    }
+
+    fn validate(
+        entry: u64,
+        phdrs: &[ProgHeader64],
+        space: AddrSpace,
+    ) -> Result<(), LayoutError> {
+        let buf = image(entry, phdrs);
+        ELF::parse(&buf)
+            .unwrap()
+            .validate_layout(space, 0x400000..0x800000)
+    }
+
+    #[test]
+    fn valid_layout() {
+        let text = load(0x1000, 0x401000, 0x800, 0x1000);
+        let data = load(0x1800, 0x402800, 0x100, 0x2000);
+        let phdrs = [text, data];
+        assert_eq!(validate(0x401000, &phdrs, AddrSpace::Virtual), Ok(()));
+        assert_eq!(
+            validate(0x1000, &phdrs, AddrSpace::Physical),
+            Err(LayoutError::OutOfWindow(0))
+        );
+    }
+
+    #[test]
+    fn layout_errors() {
+        let mut text = load(0x1000, 0x401000, 0x800, 0x1000);
+        let entry = 0x401000;
+        let mut bad_align = load(0x1000, 0x402000, 0, 0x1000);
+        bad_align.p_align = 0x1800;
+        let mut data = load(0x1000, 0x402000, 0, 0x1000);
+        data.p_flags = PFlags::R.bits();
+        let cases = [
+            (
+                load(0x1000, 0x402000, 0x1001, 0x1000),
+                LayoutError::FileSizeExceedsMemSize(1),
+            ),
+            (bad_align, LayoutError::BadAlign(1)),
+            (
+                load(0x1010, 0x402000, 0, 0x1000),
+                LayoutError::Misaligned(1),
+            ),
+            (
+                load(0x1000, 0x7ff000, 0, 0x2000),
+                LayoutError::OutOfWindow(1),
+            ),
+            (
+                load(0x1000, 0x40_0000, 0, u64::MAX),
+                LayoutError::OutOfWindow(1),
+            ),
+            (
+                load(0x1800, 0x401800, 0, 0x1000),
+                LayoutError::Overlap(0, 1),
+            ),
+        ];
+        for (phdr, err) in cases.iter() {
+            assert_eq!(
+                validate(entry, &[text, *phdr], AddrSpace::Virtual),
+                Err(*err)
+            );
+        }
+        // The physical addresses must be congruent to the offsets too.
+        text.p_paddr = text.p_vaddr;
+        data.p_paddr = 0x402010;
+        assert_eq!(validate(entry, &[text, data], AddrSpace::Virtual), Ok(()));
+        assert_eq!(
+            validate(entry, &[text, data], AddrSpace::Physical),
+            Err(LayoutError::Misaligned(1))
+        );
+        data.p_paddr = 0x403000;
+        assert_eq!(validate(entry, &[text, data], AddrSpace::Physical), Ok(()));
+        // The entry point is outside of the segments, or in the data one.
+        for &entry in [0x400000, 0x402000].iter() {
+            assert_eq!(
+                validate(entry, &[text, data], AddrSpace::Virtual),
+                Err(LayoutError::BadEntry(entry))
+            );
+        }
+    }
+}
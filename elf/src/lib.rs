@@ -1,23 +1,112 @@
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(feature = "deny-warnings", deny(warnings))]
 
+pub mod dynamic;
+mod error;
 pub mod fmt;
+pub mod layout;
+pub mod note;
+pub mod reloc;
+pub mod section;
+pub mod symbol;
+#[cfg(test)]
+mod testutil;
+pub mod tls;
+pub mod writer;
 
-use core::slice;
+use core::mem::size_of;
+use core::ptr;
 
+pub use error::{LayoutError, ParseError, RelocError, WriteError};
 use fmt::*;
 
+const ELF_MAGIC: u32 = 0x464C457F;
+const EV_CURRENT: u32 = 1;
+
 #[derive(Debug)]
-pub struct ELF {
+pub struct ELF<'a> {
+    class: EIClass,
+    // The ELF header, normalized to 64 bits.
     hdr: ELFHeader64,
-    inp: *const u8,
+    inp: &'a [u8],
+    // e_shnum, or 0 if the section header table is not available.
+    shnum: u16,
+}
+
+// Reads a T at `off`, if it is inside of `inp`.
+// T must be one of the plain-old-data headers in `fmt`.
+pub(crate) fn read<T: Copy>(inp: &[u8], off: u64) -> Option<T> {
+    let off = off as usize;
+    match off.checked_add(size_of::<T>()) {
+        Some(end) if end <= inp.len() => unsafe {
+            Some(ptr::read_unaligned(inp.as_ptr().add(off) as *const T))
+        },
+        _ => None,
+    }
+}
+
+// The bytes of `v`. T must be one of the plain-old-data headers in `fmt`,
+// which have no padding.
+pub(crate) fn bytes_of<T: Copy>(v: &T) -> &[u8] {
+    unsafe {
+        core::slice::from_raw_parts(v as *const T as *const u8, size_of::<T>())
+    }
+}
+
+// Reads a program header of `class` at `off`, normalized to 64 bits.
+pub(crate) fn read_phdr(
+    inp: &[u8],
+    class: EIClass,
+    off: u64,
+) -> Option<ProgHeader64> {
+    match class {
+        EIClass::Bit32 => read::<ProgHeader32>(inp, off).map(From::from),
+        EIClass::Bit64 => read::<ProgHeader64>(inp, off),
+    }
+}
+
+// Reads a section header of `class` at `off`, normalized to 64 bits.
+pub(crate) fn read_shdr(
+    inp: &[u8],
+    class: EIClass,
+    off: u64,
+) -> Option<SectHeader64> {
+    match class {
+        EIClass::Bit32 => read::<SectHeader32>(inp, off).map(From::from),
+        EIClass::Bit64 => read::<SectHeader64>(inp, off),
+    }
+}
+
+pub(crate) const fn phdr_size(class: EIClass) -> usize {
+    match class {
+        EIClass::Bit32 => size_of::<ProgHeader32>(),
+        EIClass::Bit64 => size_of::<ProgHeader64>(),
+    }
+}
+
+pub(crate) const fn shdr_size(class: EIClass) -> usize {
+    match class {
+        EIClass::Bit32 => size_of::<SectHeader32>(),
+        EIClass::Bit64 => size_of::<SectHeader64>(),
+    }
+}
+
+// Check that the table of `num` entries of `entsize` bytes at `off`
+// lies inside of `inp`.
+fn in_bounds(inp: &[u8], off: u64, entsize: u16, num: u16) -> bool {
+    match off.checked_add(entsize as u64 * num as u64) {
+        Some(end) => end <= inp.len() as u64,
+        None => false,
+    }
 }
 
 #[derive(Debug)]
 pub struct PhdrIter<'a> {
-    pub phdrs: &'a [ProgHeader64],
-    pub cursor: u16,
-    pub size: u16,
+    inp: &'a [u8],
+    class: EIClass,
+    off: u64,
+    cursor: u16,
+    size: u16,
 }
 
 impl<'a> Iterator for PhdrIter<'a> {
@@ -25,25 +114,100 @@ impl<'a> Iterator for PhdrIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.size > self.cursor {
-            let result = self.phdrs[self.cursor as usize];
+            let off =
+                self.off + self.cursor as u64 * phdr_size(self.class) as u64;
             self.cursor += 1;
-            return Some(result);
+            return read_phdr(self.inp, self.class, off);
         }
         None
     }
 }
 
-impl<'a> ELF {
-    pub fn new(inp: *const u8) -> Result<Self, ()> {
-        unsafe {
-            match (inp as *mut ELFHeader64).as_ref() {
-                Some(hdr) if hdr.ei_magic == 0x464C457F => Ok(ELF {
-                    hdr: *hdr,
-                    inp: inp,
-                }),
-                _ => Err(()),
+impl<'a> ELF<'a> {
+    /// Parse the ELF image in `inp`.
+    ///
+    /// The header is checked to describe a little-endian x86_64 image, or an
+    /// i386 image for the 32 bit class. Both the program header table and the
+    /// section header table must lie inside of `inp`.
+    pub fn parse(inp: &'a [u8]) -> Result<Self, ParseError> {
+        let mut elf = Self::parse_segments(inp)?;
+        let hdr = &elf.hdr;
+        if hdr.e_shnum != 0 {
+            if hdr.e_shentsize as usize != shdr_size(elf.class) {
+                return Err(ParseError::BadShdrSize(hdr.e_shentsize));
+            }
+            if !in_bounds(inp, hdr.e_shoff, hdr.e_shentsize, hdr.e_shnum) {
+                return Err(ParseError::ShdrsOutOfBounds);
             }
         }
+        elf.shnum = hdr.e_shnum;
+        Ok(elf)
+    }
+
+    /// Like `parse`, but only the program header table is required to lie
+    /// inside of `inp`. This is for loaders that only hold the front of the
+    /// image; the section header table is treated as absent.
+    pub fn parse_segments(inp: &'a [u8]) -> Result<Self, ParseError> {
+        // e_ident is common to both of the classes.
+        let ident = read::<[u8; 16]>(inp, 0).ok_or(ParseError::TooShort)?;
+        if ident[..4] != ELF_MAGIC.to_le_bytes() {
+            return Err(ParseError::BadMagic);
+        }
+        let (class, hdr, ehsize, machine) = match ident[4] {
+            1 => (
+                EIClass::Bit32,
+                read::<ELFHeader32>(inp, 0)
+                    .ok_or(ParseError::TooShort)?
+                    .into(),
+                size_of::<ELFHeader32>(),
+                EMachine::X86,
+            ),
+            2 => (
+                EIClass::Bit64,
+                read::<ELFHeader64>(inp, 0).ok_or(ParseError::TooShort)?,
+                size_of::<ELFHeader64>(),
+                EMachine::X8664,
+            ),
+            v => return Err(ParseError::UnsupportedClass(v)),
+        };
+        if hdr.ei_data != EIData::LEndian as u8 {
+            return Err(ParseError::UnsupportedEncoding(hdr.ei_data));
+        }
+        if hdr.ei_version as u32 != EV_CURRENT {
+            return Err(ParseError::UnsupportedVersion(hdr.ei_version as u32));
+        }
+        if hdr.e_version != EV_CURRENT {
+            return Err(ParseError::UnsupportedVersion(hdr.e_version));
+        }
+        if hdr.e_machine != machine as u16 {
+            return Err(ParseError::UnsupportedMachine(hdr.e_machine));
+        }
+        if hdr.e_ehsize as usize != ehsize {
+            return Err(ParseError::BadHeaderSize(hdr.e_ehsize));
+        }
+        if hdr.e_phnum != 0 {
+            if hdr.e_phentsize as usize != phdr_size(class) {
+                return Err(ParseError::BadPhdrSize(hdr.e_phentsize));
+            }
+            if !in_bounds(inp, hdr.e_phoff, hdr.e_phentsize, hdr.e_phnum) {
+                return Err(ParseError::PhdrsOutOfBounds);
+            }
+        }
+        Ok(ELF {
+            class,
+            hdr,
+            inp,
+            shnum: 0,
+        })
+    }
+
+    pub const fn class(&self) -> EIClass {
+        self.class
+    }
+
+    /// The ELF header, normalized to 64 bits.
+    pub const fn header(&self) -> &ELFHeader64 {
+        &self.hdr
     }
 
     pub const fn entry(&self) -> u64 {
@@ -54,14 +218,153 @@ impl<'a> ELF {
         self.hdr.e_phoff
     }
 
-    pub unsafe fn phdrs(&self) -> PhdrIter<'a> {
+    /// Translate `size` bytes at the virtual address `vaddr` to their offset
+    /// in the image, if a loadable segment holds them in the file.
+    pub fn vaddr_to_offset(&self, vaddr: u64, size: u64) -> Option<u64> {
+        let end = vaddr.checked_add(size)?;
+        let phdr = self.phdrs().find(|phdr| {
+            phdr.is_load()
+                && phdr.p_vaddr <= vaddr
+                && end <= phdr.p_vaddr.saturating_add(phdr.p_filesz)
+        })?;
+        let off = phdr.p_offset.checked_add(vaddr - phdr.p_vaddr)?;
+        match off.checked_add(size) {
+            Some(end) if end <= self.inp.len() as u64 => Some(off),
+            _ => None,
+        }
+    }
+
+    /// The `size` bytes at the virtual address `vaddr`, if a loadable
+    /// segment holds them in the file.
+    pub fn data_at(&self, vaddr: u64, size: u64) -> Option<&'a [u8]> {
+        let off = self.vaddr_to_offset(vaddr, size)? as usize;
+        self.inp.get(off..off + size as usize)
+    }
+
+    pub fn phdrs(&self) -> PhdrIter<'a> {
         PhdrIter {
+            inp: self.inp,
+            class: self.class,
+            off: self.phdr_off(),
+            cursor: 0,
             size: self.hdr.e_phnum,
-            phdrs: slice::from_raw_parts(
-                (self.inp as u64 + self.phdr_off()) as *mut ProgHeader64,
-                self.hdr.e_phnum as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The header of an x86_64 executable with neither of the tables.
+    fn header() -> ELFHeader64 {
+        ELFHeader64 {
+            ei_magic: ELF_MAGIC,
+            ei_class: EIClass::Bit64 as u8,
+            ei_data: EIData::LEndian as u8,
+            ei_version: EV_CURRENT as u8,
+            ei_osabi: OSabi::SystemV as u8,
+            ei_abiversion: 0,
+            ei_pad: [0; 7],
+            e_type: EType::EXEC as u16,
+            e_machine: EMachine::X8664 as u16,
+            e_version: EV_CURRENT,
+            e_entry: 0,
+            e_phoff: size_of::<ELFHeader64>() as u64,
+            e_shoff: 0,
+            e_flags: 0,
+            e_ehsize: size_of::<ELFHeader64>() as u16,
+            e_phentsize: size_of::<ProgHeader64>() as u16,
+            e_phnum: 0,
+            e_shentsize: size_of::<SectHeader64>() as u16,
+            e_shnum: 0,
+            e_shstrndx: 0,
+        }
+    }
+
+    // A change to a valid header that makes `parse` fail.
+    type Edit = fn(&mut ELFHeader64);
+
+    fn parse(hdr: &ELFHeader64) -> Result<(), ParseError> {
+        let mut inp = bytes_of(hdr).to_vec();
+        inp.resize(0x100, 0);
+        ELF::parse(&inp).map(|_| ())
+    }
+
+    #[test]
+    fn parse_header() {
+        assert_eq!(parse(&header()), Ok(()));
+        let hdr = header();
+        assert_eq!(
+            ELF::parse(&bytes_of(&hdr)[..0x20]).map(|_| ()),
+            Err(ParseError::TooShort)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases: [(Edit, ParseError); 11] = [
+            (|h| h.ei_magic = 0, ParseError::BadMagic),
+            (|h| h.ei_class = 3, ParseError::UnsupportedClass(3)),
+            (|h| h.ei_data = 2, ParseError::UnsupportedEncoding(2)),
+            (|h| h.ei_version = 0, ParseError::UnsupportedVersion(0)),
+            (|h| h.e_version = 2, ParseError::UnsupportedVersion(2)),
+            (|h| h.e_machine = 0x3, ParseError::UnsupportedMachine(0x3)),
+            (|h| h.e_ehsize = 0x34, ParseError::BadHeaderSize(0x34)),
+            (
+                |h| {
+                    h.e_phnum = 1;
+                    h.e_phentsize = 0x20;
+                },
+                ParseError::BadPhdrSize(0x20),
             ),
-            cursor: 0,
+            (
+                |h| {
+                    h.e_shnum = 1;
+                    h.e_shentsize = 0x28;
+                },
+                ParseError::BadShdrSize(0x28),
+            ),
+            (
+                |h| {
+                    h.e_phnum = 4;
+                    h.e_phoff = 0x100 - 0x38;
+                },
+                ParseError::PhdrsOutOfBounds,
+            ),
+            (
+                |h| {
+                    h.e_shnum = 1;
+                    h.e_shoff = u64::MAX;
+                },
+                ParseError::ShdrsOutOfBounds,
+            ),
+        ];
+        for (edit, err) in cases.iter() {
+            let mut hdr = header();
+            edit(&mut hdr);
+            assert_eq!(parse(&hdr), Err(*err));
         }
     }
+
+    #[test]
+    fn vaddr_to_offset_overflow() {
+        let phdr = ProgHeader64 {
+            p_type: PType::LOAD as u32,
+            p_flags: PFlags::R.bits(),
+            p_offset: u64::MAX - 0x10,
+            p_vaddr: 0x1000,
+            p_paddr: 0,
+            p_filesz: 0x100,
+            p_memsz: 0x100,
+            p_align: 0,
+        };
+        let mut hdr = header();
+        hdr.e_phnum = 1;
+        let mut inp = bytes_of(&hdr).to_vec();
+        inp.extend_from_slice(bytes_of(&phdr));
+        let elf = ELF::parse(&inp).unwrap();
+        assert_eq!(elf.vaddr_to_offset(0x1000, 1), None);
+        assert_eq!(elf.vaddr_to_offset(0x1080, 1), None);
+    }
 }
@@ -0,0 +1,306 @@
+use super::fmt::*;
+use super::{bytes_of, read, ELF};
+use core::fmt;
+use core::mem::size_of;
+
+/// Note type of the GNU build-id, named "GNU".
+pub const NT_GNU_BUILD_ID: u32 = 3;
+/// Note type of the thread status of a core dump, named "CORE".
+pub const NT_PRSTATUS: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Note<'a> {
+    /// Name of the owner of the note, without the terminating NUL.
+    pub name: &'a [u8],
+    pub ntype: u32,
+    pub desc: &'a [u8],
+}
+
+impl<'a> Note<'a> {
+    pub fn is_gnu_build_id(&self) -> bool {
+        self.name == b"GNU" && self.ntype == NT_GNU_BUILD_ID
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteIter<'a> {
+    data: &'a [u8],
+    align: usize,
+    cursor: usize,
+}
+
+impl<'a> NoteIter<'a> {
+    /// Iterate the notes in `data`, the contents of a note segment or
+    /// section aligned to `align` bytes. Alignments other than 8 are taken as
+    /// 4, as most of the notes are.
+    pub const fn new(data: &'a [u8], align: u64) -> Self {
+        NoteIter {
+            data,
+            align: if align == 8 { 8 } else { 4 },
+            cursor: 0,
+        }
+    }
+
+    // Take `len` bytes at the cursor, and advance the cursor over them along
+    // with the padding.
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.cursor.checked_add(len)?;
+        let result = self.data.get(self.cursor..end)?;
+        self.cursor = (end + self.align - 1) & !(self.align - 1);
+        Some(result)
+    }
+}
+
+impl<'a> Iterator for NoteIter<'a> {
+    type Item = Note<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hdr = read::<NoteHeader>(self.data, self.cursor as u64)?;
+        self.cursor += size_of::<NoteHeader>();
+        let name = self.take(hdr.n_namesz as usize);
+        let desc = self.take(hdr.n_descsz as usize);
+        match (name, desc) {
+            (Some(name), Some(desc)) => Some(Note {
+                name: match name.split_last() {
+                    Some((0, name)) => name,
+                    _ => name,
+                },
+                ntype: hdr.n_type,
+                desc,
+            }),
+            _ => {
+                // Stop at the truncated note.
+                self.cursor = self.data.len();
+                None
+            }
+        }
+    }
+}
+
+/// A GNU build-id. Displayed in hex as readelf does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildId<'a>(pub &'a [u8]);
+
+impl fmt::Display for BuildId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> BuildId<'a> {
+    /// The NT_GNU_BUILD_ID note of this build-id.
+    pub const fn note(&self) -> Note<'a> {
+        Note {
+            name: b"GNU",
+            ntype: NT_GNU_BUILD_ID,
+            desc: self.0,
+        }
+    }
+}
+
+impl<'a> NoteIter<'a> {
+    /// Find the GNU build-id among the notes.
+    pub fn build_id(mut self) -> Option<BuildId<'a>> {
+        self.find(Note::is_gnu_build_id)
+            .map(|note| BuildId(note.desc))
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// The notes in the segment `phdr`, which must be NOTE.
+    pub fn segment_notes(&self, phdr: &ProgHeader64) -> Option<NoteIter<'a>> {
+        if phdr.kind() != Some(PType::NOTE) {
+            return None;
+        }
+        let start = phdr.p_offset as usize;
+        let data = self
+            .inp
+            .get(start..start.checked_add(phdr.p_filesz as usize)?)?;
+        Some(NoteIter::new(data, phdr.p_align))
+    }
+
+    /// The notes in the section `shdr`, which must be NOTE.
+    pub fn section_notes(&self, shdr: &SectHeader64) -> Option<NoteIter<'a>> {
+        if shdr.kind() != Some(SHType::NOTE) {
+            return None;
+        }
+        Some(NoteIter::new(self.section_data(shdr)?, shdr.sh_addralign))
+    }
+
+    /// The GNU build-id, looked up in the note segments first and then in
+    /// the note sections.
+    pub fn build_id(&self) -> Option<BuildId<'a>> {
+        self.phdrs()
+            .filter_map(|phdr| self.segment_notes(&phdr)?.build_id())
+            .next()
+            .or_else(|| {
+                self.shdrs()
+                    .filter_map(|shdr| self.section_notes(&shdr)?.build_id())
+                    .next()
+            })
+    }
+}
+
+/// The general purpose registers of x86_64, in the order of the Linux
+/// `user_regs_struct`.
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct UserRegs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+/// The descriptor of NT_PRSTATUS on x86_64, laid out as the Linux
+/// `elf_prstatus` so that gdb can read the registers of a core dump.
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct PrStatus {
+    // Number, code and errno of the signal that killed the thread.
+    pub si_signo: i32,
+    pub si_code: i32,
+    pub si_errno: i32,
+    // Current signal.
+    pub pr_cursig: i16,
+    pub _pad0: u16,
+    // Pending and held signals.
+    pub pr_sigpend: u64,
+    pub pr_sighold: u64,
+    pub pr_pid: i32,
+    pub pr_ppid: i32,
+    pub pr_pgrp: i32,
+    pub pr_sid: i32,
+    // User, system and children's user and system times, as (sec, usec).
+    pub pr_utime: [i64; 2],
+    pub pr_stime: [i64; 2],
+    pub pr_cutime: [i64; 2],
+    pub pr_cstime: [i64; 2],
+    pub pr_reg: UserRegs,
+    // Whether a floating point register note follows.
+    pub pr_fpvalid: i32,
+    pub _pad1: u32,
+}
+
+impl PrStatus {
+    /// The NT_PRSTATUS note of this status.
+    pub fn note(&self) -> Note<'_> {
+        Note {
+            name: b"CORE",
+            ntype: NT_PRSTATUS,
+            desc: bytes_of(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{image, with_sections, Section};
+
+    // The notes ("GNU", 3, [1, 2, 3]) and ("CORE", 1, [4; 5]), padded to
+    // `align`.
+    fn notes(align: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let pad = |data: &mut Vec<u8>| {
+            data.resize((data.len() + align - 1) & !(align - 1), 0xee)
+        };
+        for (name, ntype, desc) in
+            [(&b"GNU"[..], 3, &[1, 2, 3][..]), (b"CORE", 1, &[4; 5])].iter()
+        {
+            let hdr = NoteHeader {
+                n_namesz: name.len() as u32 + 1,
+                n_descsz: desc.len() as u32,
+                n_type: *ntype,
+            };
+            data.extend_from_slice(bytes_of(&hdr));
+            data.extend_from_slice(name);
+            data.push(0);
+            pad(&mut data);
+            data.extend_from_slice(desc);
+            pad(&mut data);
+        }
+        data
+    }
+
+    #[test]
+    fn note_padding() {
+        for &align in [4, 8].iter() {
+            let data = notes(align);
+            let read = NoteIter::new(&data, align as u64).collect::<Vec<_>>();
+            assert_eq!(read.len(), 2);
+            assert_eq!(read[0].name, b"GNU");
+            assert_eq!(read[0].ntype, 3);
+            assert_eq!(read[0].desc, [1, 2, 3]);
+            assert_eq!(read[1].name, b"CORE");
+            assert_eq!(read[1].ntype, 1);
+            assert_eq!(read[1].desc, [4; 5]);
+        }
+        // Other alignments are taken as 4.
+        let data = notes(4);
+        assert_eq!(NoteIter::new(&data, 0).count(), 2);
+    }
+
+    #[test]
+    fn truncated_note() {
+        let data = notes(4);
+        let mut iter = NoteIter::new(&data[..data.len() - 4], 4);
+        assert!(iter.next().is_some());
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn build_id() {
+        let id = [0xde, 0xad, 0xbe, 0xef];
+        let other = Note {
+            name: b"CORE",
+            ntype: NT_PRSTATUS,
+            desc: &[0; 8],
+        };
+
+        let buf = image(EType::EXEC, |writer| {
+            writer.notes(&[other, BuildId(&id).note()]).unwrap();
+        });
+        let elf = ELF::parse(&buf).unwrap();
+        assert_eq!(elf.build_id(), Some(BuildId(&id)));
+        assert_eq!(format!("{}", BuildId(&id)), "deadbeef");
+
+        // Without a note segment, the build-id is found in the sections.
+        let data = notes(4);
+        let mut note = Section::new(".note.gnu.build-id", SHType::NOTE, &data);
+        note.flags = SHFlags64::ALLOC;
+        let buf = with_sections(image(EType::EXEC, |_| ()), &[note]);
+        let elf = ELF::parse(&buf).unwrap();
+        assert_eq!(elf.build_id(), Some(BuildId(&[1, 2, 3])));
+
+        let buf = image(EType::EXEC, |writer| writer.notes(&[other]).unwrap());
+        assert_eq!(ELF::parse(&buf).unwrap().build_id(), None);
+    }
+}
@@ -0,0 +1,362 @@
+use super::error::RelocError;
+use super::fmt::*;
+use super::{read, ELF};
+use core::convert::TryFrom;
+use core::mem::size_of;
+
+#[derive(Debug, Clone)]
+pub struct RelaIter<'a> {
+    data: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> RelaIter<'a> {
+    /// Iterate the relocations in `data`, the contents of a RELA section
+    /// or the table that DT_RELA points to.
+    pub const fn new(data: &'a [u8]) -> Self {
+        RelaIter { data, cursor: 0 }
+    }
+}
+
+impl<'a> Iterator for RelaIter<'a> {
+    type Item = Rela64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = read(self.data, self.cursor as u64)?;
+        self.cursor += size_of::<Rela64>();
+        Some(result)
+    }
+}
+
+/// Applies relocations to a loaded image.
+///
+/// `mem` holds the image as laid out by its program headers, starting from
+/// the link-time virtual address `vaddr_base`. The image runs at `slide`
+/// bytes from its link-time addresses.
+#[derive(Debug)]
+pub struct Relocator<'b> {
+    mem: &'b mut [u8],
+    vaddr_base: u64,
+    slide: u64,
+}
+
+impl<'b> Relocator<'b> {
+    pub fn new(mem: &'b mut [u8], vaddr_base: u64, slide: u64) -> Self {
+        Relocator {
+            mem,
+            vaddr_base,
+            slide,
+        }
+    }
+
+    fn read(&self, vaddr: u64, bytes: &mut [u8]) -> Result<(), RelocError> {
+        let off = vaddr
+            .checked_sub(self.vaddr_base)
+            .ok_or(RelocError::OutOfBounds(vaddr))? as usize;
+        bytes.copy_from_slice(
+            self.mem
+                .get(off..off.saturating_add(bytes.len()))
+                .ok_or(RelocError::OutOfBounds(vaddr))?,
+        );
+        Ok(())
+    }
+
+    fn write(&mut self, vaddr: u64, bytes: &[u8]) -> Result<(), RelocError> {
+        let off = vaddr
+            .checked_sub(self.vaddr_base)
+            .ok_or(RelocError::OutOfBounds(vaddr))? as usize;
+        self.mem
+            .get_mut(off..off.saturating_add(bytes.len()))
+            .ok_or(RelocError::OutOfBounds(vaddr))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Apply `rela`. `resolve` maps a symbol index to the run-time address
+    /// of the symbol.
+    pub fn apply<F>(
+        &mut self,
+        rela: &Rela64,
+        resolve: F,
+    ) -> Result<(), RelocError>
+    where
+        F: FnOnce(u32) -> Option<u64>,
+    {
+        let rtype = RelocX8664::try_from(rela.rtype())
+            .map_err(RelocError::Unsupported)?;
+        let a = rela.r_addend as u64;
+        let p = rela.r_offset.wrapping_add(self.slide);
+        let sym = rela.sym();
+        let s = || match sym {
+            0 => Ok(0),
+            _ => resolve(sym).ok_or(RelocError::UndefinedSymbol(sym)),
+        };
+
+        match rtype {
+            RelocX8664::NONE => Ok(()),
+            RelocX8664::RELATIVE => {
+                let v = self.slide.wrapping_add(a);
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            RelocX8664::R64 => {
+                let v = s()?.wrapping_add(a);
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            RelocX8664::GLOBDAT | RelocX8664::JUMPSLOT => {
+                let v = s()?;
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            RelocX8664::R32 => {
+                let v = u32::try_from(s()?.wrapping_add(a))
+                    .map_err(|_| RelocError::Overflow(rela.r_offset))?;
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            RelocX8664::R32S => {
+                let v = i32::try_from(s()?.wrapping_add(a) as i64)
+                    .map_err(|_| RelocError::Overflow(rela.r_offset))?;
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            // A linked image calls the functions directly, without a PLT.
+            RelocX8664::PC32 | RelocX8664::PLT32 => {
+                let v = s()?.wrapping_add(a).wrapping_sub(p) as i64;
+                let v = i32::try_from(v)
+                    .map_err(|_| RelocError::Overflow(rela.r_offset))?;
+                self.write(rela.r_offset, &v.to_le_bytes())
+            }
+            // The field holds the distance to the GOT slot of the symbol,
+            // which the linker filled in without a relocation of its own, or
+            // to the symbol itself if the linker relaxed the load into a
+            // lea. Either distance stays, but the slot moves with the symbol.
+            RelocX8664::GOTPCREL
+            | RelocX8664::GOTPCRELX
+            | RelocX8664::REXGOTPCRELX => {
+                let s = s()?;
+                let mut field = [0; 4];
+                self.read(rela.r_offset, &mut field)?;
+                let target = rela
+                    .r_offset
+                    .wrapping_add(i32::from_le_bytes(field) as u64)
+                    .wrapping_sub(a);
+                if target.wrapping_add(self.slide) == s {
+                    return Ok(());
+                }
+                let mut slot = [0; 8];
+                self.read(target, &mut slot)?;
+                // Many loads may share the slot, which is moved only once.
+                match u64::from_le_bytes(slot) {
+                    v if v == s => Ok(()),
+                    v if v.wrapping_add(self.slide) == s => {
+                        self.write(target, &s.to_le_bytes())
+                    }
+                    _ => Err(RelocError::BadGotSlot(rela.r_offset)),
+                }
+            }
+            _ => Err(RelocError::Unsupported(rela.rtype())),
+        }
+    }
+
+    /// Apply every relocation of `relas`, stopping at the first error.
+    pub fn apply_all<I, F>(
+        &mut self,
+        relas: I,
+        mut resolve: F,
+    ) -> Result<(), RelocError>
+    where
+        I: IntoIterator<Item = Rela64>,
+        F: FnMut(u32) -> Option<u64>,
+    {
+        for rela in relas {
+            self.apply(&rela, &mut resolve)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// The relocations in the section `shdr`, which must be RELA.
+    pub fn relas(&self, shdr: &SectHeader64) -> Option<RelaIter<'a>> {
+        if shdr.kind() != Some(SHType::RELA)
+            || shdr.sh_entsize as usize != size_of::<Rela64>()
+        {
+            return None;
+        }
+        self.section_data(shdr).map(RelaIter::new)
+    }
+
+    /// The relocations that DT_RELA of the dynamic segment points to.
+    /// Only 64 bit images are supported.
+    pub fn dynamic_relas(&self) -> Option<RelaIter<'a>> {
+        if self.class != EIClass::Bit64 {
+            return None;
+        }
+        self.dynamic()?.relas()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x400000;
+    const SLIDE: u64 = 0x10000;
+
+    fn rela(offset: u64, sym: u32, rtype: RelocX8664, addend: i64) -> Rela64 {
+        Rela64 {
+            r_offset: BASE + offset,
+            r_info: (sym as u64) << 32 | rtype as u64,
+            r_addend: addend,
+        }
+    }
+
+    // Apply `rela` to a zeroed image of 0x40 bytes, in which the symbol 1
+    // is at `sym`, and return the image.
+    fn apply(rela: Rela64, sym: u64) -> Result<[u8; 0x40], RelocError> {
+        let mut mem = [0; 0x40];
+        Relocator::new(&mut mem, BASE, SLIDE).apply(
+            &rela,
+            |index| match index {
+                1 => Some(sym),
+                _ => None,
+            },
+        )?;
+        Ok(mem)
+    }
+
+    fn u64_at(mem: &[u8], off: usize) -> u64 {
+        let mut v = [0; 8];
+        v.copy_from_slice(&mem[off..off + 8]);
+        u64::from_le_bytes(v)
+    }
+
+    fn i32_at(mem: &[u8], off: usize) -> i32 {
+        let mut v = [0; 4];
+        v.copy_from_slice(&mem[off..off + 4]);
+        i32::from_le_bytes(v)
+    }
+
+    #[test]
+    fn relative() {
+        let mem = apply(rela(8, 0, RelocX8664::RELATIVE, 0x400100), 0).unwrap();
+        assert_eq!(u64_at(&mem, 8), 0x410100);
+    }
+
+    #[test]
+    fn absolute() {
+        let mem = apply(rela(0, 1, RelocX8664::R64, 8), 0x500000).unwrap();
+        assert_eq!(u64_at(&mem, 0), 0x500008);
+        let mem = apply(rela(0, 1, RelocX8664::R64, -8), 0x500000).unwrap();
+        assert_eq!(u64_at(&mem, 0), 0x4ffff8);
+    }
+
+    #[test]
+    fn pc_relative() {
+        for &rtype in [RelocX8664::PC32, RelocX8664::PLT32].iter() {
+            // The place runs at BASE + SLIDE + 0x10.
+            let rela = rela(0x10, 1, rtype, -4);
+            let mem = apply(rela, 0x410020).unwrap();
+            assert_eq!(i32_at(&mem, 0x10), 0xc);
+            let mem = apply(rela, 0x400000).unwrap();
+            assert_eq!(i32_at(&mem, 0x10), -0x10014);
+        }
+    }
+
+    // Apply `rela` to an image whose field at 0x10 holds `field`, and whose
+    // GOT slot at 0x20 holds `slot`, in which the symbol 1 is at `sym`, and
+    // return the slot.
+    fn load_got(field: i32, slot: u64, sym: u64) -> Result<u64, RelocError> {
+        let mut mem = [0; 0x40];
+        mem[0x10..0x14].copy_from_slice(&field.to_le_bytes());
+        mem[0x20..0x28].copy_from_slice(&slot.to_le_bytes());
+        let mut relocator = Relocator::new(&mut mem, BASE, SLIDE);
+        for &rtype in [
+            RelocX8664::GOTPCREL,
+            RelocX8664::GOTPCRELX,
+            RelocX8664::REXGOTPCRELX,
+        ]
+        .iter()
+        {
+            relocator.apply(&rela(0x10, 1, rtype, -4), |_| Some(sym))?;
+        }
+        assert_eq!(i32_at(&mem, 0x10), field);
+        Ok(u64_at(&mem, 0x20))
+    }
+
+    #[test]
+    fn got_slot() {
+        // The field is the distance from the end of the instruction.
+        let to_slot = 0x20 - 0x14;
+        // The slot moves with the symbol, once.
+        let sym = BASE + 0x30;
+        assert_eq!(load_got(to_slot, sym, sym + SLIDE), Ok(sym + SLIDE));
+        // An absolute symbol stays.
+        assert_eq!(load_got(to_slot, 0x1234, 0x1234), Ok(0x1234));
+        // A lea of the symbol, which the linker relaxed, only stays.
+        assert_eq!(load_got(0x30 - 0x14, 0, sym + SLIDE), Ok(0));
+
+        assert_eq!(
+            load_got(to_slot, sym + 8, sym + SLIDE),
+            Err(RelocError::BadGotSlot(BASE + 0x10))
+        );
+        assert_eq!(
+            load_got(0x40 - 0x14, sym, sym + SLIDE),
+            Err(RelocError::OutOfBounds(BASE + 0x40))
+        );
+    }
+
+    #[test]
+    fn got_and_plt() {
+        for &rtype in [RelocX8664::GLOBDAT, RelocX8664::JUMPSLOT].iter() {
+            // The addend is not used.
+            let mem = apply(rela(0x18, 1, rtype, 0x100), 0x7000_1234).unwrap();
+            assert_eq!(u64_at(&mem, 0x18), 0x7000_1234);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let pc32 = rela(0x10, 1, RelocX8664::PC32, 0);
+        let far = BASE + SLIDE + 0x10 + (1 << 31);
+        assert_eq!(apply(pc32, far), Err(RelocError::Overflow(BASE + 0x10)));
+        assert!(apply(pc32, far - 1).is_ok());
+
+        let r32s = rela(0x10, 1, RelocX8664::R32S, 0);
+        assert_eq!(
+            apply(r32s, 0x8000_0000),
+            Err(RelocError::Overflow(BASE + 0x10))
+        );
+        let mem = apply(r32s, (-0x8000_0000i64) as u64).unwrap();
+        assert_eq!(i32_at(&mem, 0x10), i32::MIN);
+    }
+
+    #[test]
+    fn unsupported() {
+        let copy = rela(0, 1, RelocX8664::COPY, 0);
+        assert_eq!(apply(copy, 0), Err(RelocError::Unsupported(5)));
+        let unknown = Rela64 {
+            r_info: 1 << 32 | 99,
+            ..copy
+        };
+        assert_eq!(apply(unknown, 0), Err(RelocError::Unsupported(99)));
+    }
+
+    #[test]
+    fn undefined_symbol() {
+        let r64 = rela(0, 2, RelocX8664::R64, 0);
+        assert_eq!(apply(r64, 0), Err(RelocError::UndefinedSymbol(2)));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        for &off in [0x39, 0x40, u64::MAX - BASE].iter() {
+            let r64 = rela(off, 1, RelocX8664::R64, 0);
+            assert_eq!(apply(r64, 0), Err(RelocError::OutOfBounds(BASE + off)));
+        }
+        let below = Rela64 {
+            r_offset: BASE - 8,
+            ..rela(0, 0, RelocX8664::RELATIVE, 0)
+        };
+        assert_eq!(apply(below, 0), Err(RelocError::OutOfBounds(BASE - 8)));
+        // A 32 bit field fits at the end.
+        assert!(apply(rela(0x3c, 1, RelocX8664::R32, 0), 0).is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+use super::fmt::*;
+use super::{read_shdr, shdr_size, ELF};
+use core::str;
+
+/// Reserved section index of SHN_UNDEF.
+pub const SHN_UNDEF: u16 = 0;
+/// Reserved section index of the absolute symbols.
+pub const SHN_ABS: u16 = 0xfff1;
+
+#[derive(Debug)]
+pub struct ShdrIter<'a> {
+    inp: &'a [u8],
+    class: EIClass,
+    off: u64,
+    cursor: u16,
+    size: u16,
+}
+
+impl<'a> Iterator for ShdrIter<'a> {
+    type Item = SectHeader64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.size > self.cursor {
+            let off =
+                self.off + self.cursor as u64 * shdr_size(self.class) as u64;
+            self.cursor += 1;
+            return read_shdr(self.inp, self.class, off);
+        }
+        None
+    }
+}
+
+/// A string table, holding NUL-terminated strings.
+#[derive(Debug, Clone, Copy)]
+pub struct StrTab<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StrTab<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        StrTab { data }
+    }
+
+    /// Get the string starting at `off`.
+    pub fn get(&self, off: u32) -> Option<&'a str> {
+        let s = self.data.get(off as usize..)?;
+        let len = s.iter().position(|&c| c == 0)?;
+        str::from_utf8(&s[..len]).ok()
+    }
+}
+
+impl<'a> ELF<'a> {
+    pub fn shdrs(&self) -> ShdrIter<'a> {
+        ShdrIter {
+            inp: self.inp,
+            class: self.class,
+            off: self.hdr.e_shoff,
+            cursor: 0,
+            size: self.shnum,
+        }
+    }
+
+    pub fn section(&self, index: u32) -> Option<SectHeader64> {
+        if index >= self.shnum as u32 {
+            return None;
+        }
+        let off = index as u64 * shdr_size(self.class) as u64;
+        read_shdr(self.inp, self.class, self.hdr.e_shoff + off)
+    }
+
+    /// Contents of the section. Sections without file contents (NOBITS) are
+    /// empty.
+    pub fn section_data(&self, shdr: &SectHeader64) -> Option<&'a [u8]> {
+        if shdr.kind() == Some(SHType::NOBITS) {
+            return Some(&[]);
+        }
+        let start = shdr.sh_offset as usize;
+        self.inp
+            .get(start..start.checked_add(shdr.sh_size as usize)?)
+    }
+
+    /// The string table linked by `shdr.sh_link`.
+    pub fn linked_strtab(&self, shdr: &SectHeader64) -> Option<StrTab<'a>> {
+        let link = self.section(shdr.sh_link)?;
+        if link.kind() != Some(SHType::STRTAB) {
+            return None;
+        }
+        self.section_data(&link).map(StrTab::new)
+    }
+
+    /// The section name string table (.shstrtab).
+    pub fn shstrtab(&self) -> Option<StrTab<'a>> {
+        match self.hdr.e_shstrndx {
+            SHN_UNDEF => None,
+            index => {
+                let shdr = self.section(index as u32)?;
+                self.section_data(&shdr).map(StrTab::new)
+            }
+        }
+    }
+
+    pub fn section_name(&self, shdr: &SectHeader64) -> Option<&'a str> {
+        self.shstrtab()?.get(shdr.sh_name)
+    }
+
+    /// Find the first section named `name`, e.g. ".symtab".
+    pub fn section_by_name(&self, name: &str) -> Option<SectHeader64> {
+        let shstrtab = self.shstrtab()?;
+        self.shdrs()
+            .find(|shdr| shstrtab.get(shdr.sh_name) == Some(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes_of;
+    use crate::testutil::{image, with_sections, Section};
+
+    fn sections() -> Vec<u8> {
+        let mut bss = Section::new(".bss", SHType::NOBITS, &[0; 0x40]);
+        bss.flags = SHFlags64::ALLOC | SHFlags64::WRITE;
+        with_sections(
+            image(EType::EXEC, |_| ()),
+            &[Section::new(".text", SHType::PROGBITS, &[0x90; 5]), bss],
+        )
+    }
+
+    #[test]
+    fn iterate_sections() {
+        let buf = sections();
+        let elf = ELF::parse(&buf).unwrap();
+        let names = elf
+            .shdrs()
+            .map(|shdr| elf.section_name(&shdr))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [Some(""), Some(".text"), Some(".bss"), Some(".shstrtab")]
+        );
+
+        let text = elf.section_by_name(".text").unwrap();
+        assert_eq!(elf.section(1).map(|shdr| shdr.sh_name), Some(text.sh_name));
+        assert_eq!(elf.section_data(&text), Some(&[0x90; 5][..]));
+        let bss = elf.section_by_name(".bss").unwrap();
+        assert_eq!(bss.sh_size, 0x40);
+        assert_eq!(elf.section_data(&bss), Some(&[][..]));
+        assert!(elf.section(4).is_none());
+        assert!(elf.section_by_name(".data").is_none());
+    }
+
+    #[test]
+    fn bad_shstrndx() {
+        let mut buf = sections();
+        for &index in [SHN_UNDEF, 4, 1].iter() {
+            let mut hdr = *ELF::parse(&buf).unwrap().header();
+            hdr.e_shstrndx = index;
+            let len = bytes_of(&hdr).len();
+            buf[..len].copy_from_slice(bytes_of(&hdr));
+
+            let elf = ELF::parse(&buf).unwrap();
+            let text = elf.section(1).unwrap();
+            assert_eq!(elf.section_name(&text), None);
+            assert!(elf.section_by_name(".text").is_none());
+        }
+    }
+}
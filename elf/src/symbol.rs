@@ -0,0 +1,312 @@
+use super::fmt::*;
+use super::section::{StrTab, SHN_UNDEF};
+use super::{read, ELF};
+use core::fmt;
+use core::mem::size_of;
+
+/// A symbol table (SYMTAB or DYNSYM) along with its string table.
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolTable<'a> {
+    class: EIClass,
+    data: &'a [u8],
+    strtab: StrTab<'a>,
+}
+
+#[derive(Debug)]
+pub struct SymIter<'a> {
+    table: SymbolTable<'a>,
+    cursor: usize,
+}
+
+impl<'a> Iterator for SymIter<'a> {
+    type Item = Symbol64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.table.get(self.cursor)?;
+        self.cursor += 1;
+        Some(result)
+    }
+}
+
+/// A symbol that an address resolved to.
+#[derive(Debug, Clone, Copy)]
+pub struct Resolved<'a> {
+    pub symbol: Symbol64,
+    pub name: &'a str,
+    /// Offset of the address from the start of the symbol.
+    pub offset: u64,
+}
+
+impl fmt::Display for Resolved<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+0x{:x}", self.name, self.offset)
+    }
+}
+
+impl<'a> SymbolTable<'a> {
+    pub const fn new(data: &'a [u8], strtab: StrTab<'a>) -> Self {
+        SymbolTable {
+            class: EIClass::Bit64,
+            data,
+            strtab,
+        }
+    }
+
+    /// A symbol table of 32 bit entries.
+    pub const fn new32(data: &'a [u8], strtab: StrTab<'a>) -> Self {
+        SymbolTable {
+            class: EIClass::Bit32,
+            data,
+            strtab,
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.data.len() / sym_size(self.class)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the symbol at `index`, normalized to 64 bits.
+    pub fn get(&self, index: usize) -> Option<Symbol64> {
+        if index >= self.len() {
+            return None;
+        }
+        let off = (index * sym_size(self.class)) as u64;
+        match self.class {
+            EIClass::Bit32 => read::<Symbol32>(self.data, off).map(From::from),
+            EIClass::Bit64 => read::<Symbol64>(self.data, off),
+        }
+    }
+
+    pub fn iter(&self) -> SymIter<'a> {
+        SymIter {
+            table: *self,
+            cursor: 0,
+        }
+    }
+
+    pub fn name(&self, sym: &Symbol64) -> Option<&'a str> {
+        self.strtab.get(sym.st_name)
+    }
+
+    // Check whether `addr` is inside of the function `sym`.
+    // Symbols without a size are assumed to extend up to `addr`.
+    fn resolve(&self, sym: Symbol64, addr: u64) -> Option<Resolved<'a>> {
+        let offset = addr.checked_sub(sym.st_value)?;
+        if sym.st_size != 0 && offset >= sym.st_size {
+            return None;
+        }
+        Some(Resolved {
+            symbol: sym,
+            name: self.name(&sym)?,
+            offset,
+        })
+    }
+
+    /// Find the function enclosing `addr` by scanning the whole table.
+    /// Use `AddrIndex` for repeated lookups.
+    pub fn enclosing(&self, addr: u64) -> Option<Resolved<'a>> {
+        self.iter()
+            .filter(|sym| is_indexable(sym) && sym.st_value <= addr)
+            .max_by_key(|sym| (sym.st_value, sym.st_size != 0))
+            .and_then(|sym| self.resolve(sym, addr))
+    }
+}
+
+const fn sym_size(class: EIClass) -> usize {
+    match class {
+        EIClass::Bit32 => size_of::<Symbol32>(),
+        EIClass::Bit64 => size_of::<Symbol64>(),
+    }
+}
+
+// Only defined functions take part in address lookups.
+fn is_indexable(sym: &Symbol64) -> bool {
+    sym.is_function() && sym.st_shndx != SHN_UNDEF && sym.st_value != 0
+}
+
+/// Functions of a symbol table sorted by address.
+///
+/// The index lives in a caller-supplied buffer, so no allocation is needed.
+#[derive(Debug)]
+pub struct AddrIndex<'a, 'b> {
+    table: SymbolTable<'a>,
+    index: &'b [u32],
+}
+
+impl<'a, 'b> AddrIndex<'a, 'b> {
+    /// Build the index in `buf`. If `buf` is too small to hold every
+    /// function, the rest of the functions are left out.
+    pub fn new(table: SymbolTable<'a>, buf: &'b mut [u32]) -> Self {
+        let mut len = 0;
+        for (i, sym) in table.iter().enumerate() {
+            if len == buf.len() {
+                break;
+            }
+            if is_indexable(&sym) {
+                buf[len] = i as u32;
+                len += 1;
+            }
+        }
+        let index = &mut buf[..len];
+        index.sort_unstable_by_key(|&i| {
+            table.get(i as usize).map(|sym| sym.st_value)
+        });
+        AddrIndex { table, index }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Find the function enclosing `addr`.
+    pub fn lookup(&self, addr: u64) -> Option<Resolved<'a>> {
+        let table = &self.table;
+        let pos = self.index.partition_point(|&i| {
+            table.get(i as usize).map_or(0, |sym| sym.st_value) <= addr
+        });
+        let sym = table.get(*self.index.get(pos.checked_sub(1)?)? as usize)?;
+        table.resolve(sym, addr)
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// The symbol table in the section `shdr`, which must be either
+    /// SYMTAB or DYNSYM.
+    pub fn symbol_table(&self, shdr: &SectHeader64) -> Option<SymbolTable<'a>> {
+        match shdr.kind() {
+            Some(SHType::SYMTAB) | Some(SHType::DYNSYM) => (),
+            _ => return None,
+        }
+        if shdr.sh_entsize as usize != sym_size(self.class) {
+            return None;
+        }
+        Some(SymbolTable {
+            class: self.class,
+            data: self.section_data(shdr)?,
+            strtab: self.linked_strtab(shdr)?,
+        })
+    }
+
+    /// The static symbol table (.symtab).
+    pub fn symtab(&self) -> Option<SymbolTable<'a>> {
+        let shdr = self
+            .shdrs()
+            .find(|shdr| shdr.kind() == Some(SHType::SYMTAB))?;
+        self.symbol_table(&shdr)
+    }
+
+    /// The dynamic symbol table (.dynsym).
+    pub fn dynsym(&self) -> Option<SymbolTable<'a>> {
+        let shdr = self
+            .shdrs()
+            .find(|shdr| shdr.kind() == Some(SHType::DYNSYM))?;
+        self.symbol_table(&shdr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes_of;
+
+    const STRTAB: &[u8] = b"\0a\0b\0c\0d\0e\0";
+
+    fn sym(
+        name: u32,
+        kind: STType,
+        shndx: u16,
+        value: u64,
+        size: u64,
+    ) -> Symbol64 {
+        Symbol64 {
+            st_name: name,
+            // STB_GLOBAL.
+            st_info: 1 << 4 | kind as u8,
+            st_other: 0,
+            st_shndx: shndx,
+            st_value: value,
+            st_size: size,
+        }
+    }
+
+    // The functions a at 0x1000-0x1010, b at 0x1020 without a size and c
+    // at 0x1040-0x1060, out of order, along with an object and an
+    // undefined function.
+    fn symbols() -> Vec<u8> {
+        [
+            sym(0, STType::NOTYPE, SHN_UNDEF, 0, 0),
+            sym(5, STType::FUNC, 1, 0x1040, 0x20),
+            sym(1, STType::FUNC, 1, 0x1000, 0x10),
+            sym(7, STType::OBJECT, 2, 0x1010, 8),
+            sym(9, STType::FUNC, SHN_UNDEF, 0x1050, 0),
+            sym(3, STType::FUNC, 1, 0x1020, 0),
+        ]
+        .iter()
+        .flat_map(|sym| bytes_of(sym).to_vec())
+        .collect()
+    }
+
+    // The name and the offset that `addr` resolves to, both through the
+    // table and through the index.
+    fn lookups(data: &[u8], addr: u64) -> [Option<(&str, u64)>; 2] {
+        let table = SymbolTable::new(data, StrTab::new(STRTAB));
+        let mut buf = [0; 8];
+        let index = AddrIndex::new(table, &mut buf);
+        assert_eq!(index.len(), 3);
+        fn found(r: Option<Resolved<'_>>) -> Option<(&str, u64)> {
+            r.map(|r| (r.name, r.offset))
+        }
+        [found(table.enclosing(addr)), found(index.lookup(addr))]
+    }
+
+    #[test]
+    fn inside_a_symbol() {
+        let data = symbols();
+        assert_eq!(lookups(&data, 0x1000), [Some(("a", 0)); 2]);
+        assert_eq!(lookups(&data, 0x100f), [Some(("a", 0xf)); 2]);
+        assert_eq!(lookups(&data, 0x105f), [Some(("c", 0x1f)); 2]);
+    }
+
+    #[test]
+    fn between_symbols() {
+        let data = symbols();
+        // Past the end of a, and not in the object.
+        assert_eq!(lookups(&data, 0x1010), [None; 2]);
+        assert_eq!(lookups(&data, 0x101f), [None; 2]);
+    }
+
+    #[test]
+    fn symbol_without_size() {
+        let data = symbols();
+        assert_eq!(lookups(&data, 0x1020), [Some(("b", 0)); 2]);
+        assert_eq!(lookups(&data, 0x103f), [Some(("b", 0x1f)); 2]);
+    }
+
+    #[test]
+    fn outside_of_the_symbols() {
+        let data = symbols();
+        assert_eq!(lookups(&data, 0), [None; 2]);
+        assert_eq!(lookups(&data, 0xfff), [None; 2]);
+        assert_eq!(lookups(&data, 0x1060), [None; 2]);
+        assert_eq!(lookups(&data, u64::MAX), [None; 2]);
+    }
+
+    #[test]
+    fn short_index() {
+        let data = symbols();
+        let table = SymbolTable::new(&data, StrTab::new(STRTAB));
+        let mut buf = [0; 1];
+        let index = AddrIndex::new(table, &mut buf);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.lookup(0x1040).map(|r| r.name), Some("c"));
+        assert!(index.lookup(0x1000).is_none());
+    }
+}
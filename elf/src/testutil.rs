@@ -0,0 +1,112 @@
+//! Hand-built images for the unit tests.
+
+use super::fmt::*;
+use super::writer::Writer;
+use super::{bytes_of, read};
+use core::mem::size_of;
+
+/// Build an image of `etype` with the segments that `build` adds.
+pub fn image<F>(etype: EType, build: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Writer<&mut [u8]>),
+{
+    let mut buf = vec![0; 0x10000];
+    let mut writer = Writer::new(&mut buf[..], etype, 8);
+    build(&mut writer);
+    let (_, size) = writer.finish().unwrap();
+    buf.truncate(size as usize);
+    buf
+}
+
+/// A section for `with_sections` to add.
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    pub name: &'a str,
+    pub kind: SHType,
+    pub flags: SHFlags64,
+    pub addr: u64,
+    pub data: &'a [u8],
+    pub link: u32,
+    pub info: u32,
+    pub entsize: u64,
+}
+
+impl<'a> Section<'a> {
+    pub fn new(name: &'a str, kind: SHType, data: &'a [u8]) -> Self {
+        Section {
+            name,
+            kind,
+            flags: SHFlags64::empty(),
+            addr: 0,
+            data,
+            link: 0,
+            info: 0,
+            entsize: 0,
+        }
+    }
+}
+
+// Append `data` to `image` at the next multiple of 8, and return its offset.
+fn append(image: &mut Vec<u8>, data: &[u8]) -> u64 {
+    image.resize((image.len() + 7) & !7, 0);
+    let off = image.len() as u64;
+    image.extend_from_slice(data);
+    off
+}
+
+/// Append the contents of `sections`, a .shstrtab and the section header
+/// table to `image`, which has none. The table starts with the null
+/// section, so the index of `sections[i]` is i + 1, and ends with the
+/// .shstrtab.
+pub fn with_sections(mut image: Vec<u8>, sections: &[Section]) -> Vec<u8> {
+    let mut names = vec![0];
+    let mut shdrs = vec![SectHeader64 {
+        sh_name: 0,
+        sh_type: SHType::NULL as u32,
+        sh_flags: 0,
+        sh_addr: 0,
+        sh_offset: 0,
+        sh_size: 0,
+        sh_link: 0,
+        sh_info: 0,
+        sh_addralign: 0,
+        sh_entsize: 0,
+    }];
+    let shstrtab = Section::new(".shstrtab", SHType::STRTAB, &[]);
+    for section in sections.iter().chain(Some(&shstrtab)) {
+        let sh_name = names.len() as u32;
+        names.extend_from_slice(section.name.as_bytes());
+        names.push(0);
+        let data = match section.kind {
+            SHType::NOBITS => &[],
+            _ => section.data,
+        };
+        shdrs.push(SectHeader64 {
+            sh_name,
+            sh_type: section.kind as u32,
+            sh_flags: section.flags.bits(),
+            sh_addr: section.addr,
+            sh_offset: append(&mut image, data),
+            sh_size: section.data.len() as u64,
+            sh_link: section.link,
+            sh_info: section.info,
+            sh_addralign: 8,
+            sh_entsize: section.entsize,
+        });
+    }
+    let last = shdrs.last_mut().unwrap();
+    last.sh_offset = append(&mut image, &names);
+    last.sh_size = names.len() as u64;
+
+    let shoff = append(&mut image, &[]);
+    for shdr in shdrs.iter() {
+        image.extend_from_slice(bytes_of(shdr));
+    }
+    let mut hdr = read::<ELFHeader64>(&image, 0).unwrap();
+    hdr.e_shoff = shoff;
+    hdr.e_shentsize = size_of::<SectHeader64>() as u16;
+    hdr.e_shnum = shdrs.len() as u16;
+    hdr.e_shstrndx = shdrs.len() as u16 - 1;
+    image[..size_of::<ELFHeader64>()].copy_from_slice(bytes_of(&hdr));
+    image
+}
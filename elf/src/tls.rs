@@ -0,0 +1,157 @@
+use super::fmt::*;
+use super::ELF;
+use core::mem::size_of;
+use core::ptr;
+
+/// The TLS template of an image (PT_TLS): the initial contents of the
+/// thread-local variables of every thread.
+#[derive(Debug, Clone, Copy)]
+pub struct TlsTemplate<'a> {
+    /// The initialized part of the template (.tdata).
+    pub image: &'a [u8],
+    /// The size of the template, including the zero-filled part (.tbss).
+    pub size: u64,
+    /// The alignment of the template, at least 1.
+    pub align: u64,
+}
+
+/// Where the parts of a variant II TLS block go, as offsets from the start
+/// of the block.
+///
+/// On x86_64, the TLS block of the executable ends at the thread pointer
+/// (the FS base), and the thread control block starts there. The first word
+/// of the thread control block points to itself, so that the thread pointer
+/// can be read through %fs:0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsLayout {
+    /// The size of the whole block.
+    pub size: u64,
+    /// The alignment of the whole block.
+    pub align: u64,
+    /// Offset of the thread-local variables.
+    pub tls: u64,
+    /// Offset of the thread pointer.
+    pub tp: u64,
+}
+
+const fn align_up(v: u64, align: u64) -> u64 {
+    (v + align - 1) & !(align - 1)
+}
+
+impl<'a> TlsTemplate<'a> {
+    /// The layout of a block with a thread control block of `tcb_size`
+    /// bytes, which must be at least a word.
+    pub fn layout(&self, tcb_size: u64) -> TlsLayout {
+        let align = self.align.max(size_of::<u64>() as u64);
+        let tp = align_up(self.size, align);
+        TlsLayout {
+            size: align_up(tp + tcb_size.max(size_of::<u64>() as u64), align),
+            align,
+            // The linker places the variables the size rounded up to their
+            // alignment below the thread pointer.
+            tls: tp - align_up(self.size, self.align),
+            tp,
+        }
+    }
+
+    /// Initialize a TLS block laid out by `layout` at `block`, which is
+    /// mapped at `base` in the thread, and return the thread pointer of the
+    /// thread.
+    ///
+    /// The thread control block other than its first word is zero-filled.
+    pub fn init_block(
+        &self,
+        layout: &TlsLayout,
+        block: &mut [u8],
+        base: u64,
+    ) -> Option<u64> {
+        if block.len() as u64 != layout.size || base & (layout.align - 1) != 0 {
+            return None;
+        }
+        for byte in block.iter_mut() {
+            *byte = 0;
+        }
+        let tls = layout.tls as usize;
+        block
+            .get_mut(tls..tls + self.image.len())?
+            .copy_from_slice(self.image);
+        let tp = base + layout.tp;
+        let tcb = block.get_mut(layout.tp as usize..)?;
+        if tcb.len() < size_of::<u64>() {
+            return None;
+        }
+        unsafe { ptr::write_unaligned(tcb.as_mut_ptr() as *mut u64, tp) };
+        Some(tp)
+    }
+}
+
+impl<'a> ELF<'a> {
+    /// The TLS template, if the image has thread-local variables.
+    pub fn tls(&self) -> Option<TlsTemplate<'a>> {
+        let phdr = self.phdrs().find(|phdr| phdr.kind() == Some(PType::TLS))?;
+        if phdr.p_filesz > phdr.p_memsz {
+            return None;
+        }
+        let align = match phdr.p_align {
+            0 => 1,
+            align if align.is_power_of_two() => align,
+            _ => return None,
+        };
+        let start = phdr.p_offset as usize;
+        let image = self
+            .inp
+            .get(start..start.checked_add(phdr.p_filesz as usize)?)?;
+        Some(TlsTemplate {
+            image,
+            size: phdr.p_memsz,
+            align,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_unaligned_size() {
+        let template = TlsTemplate {
+            image: &[1, 2, 3, 4],
+            size: 12,
+            align: 8,
+        };
+        let layout = template.layout(0x10);
+        assert_eq!(
+            layout,
+            TlsLayout {
+                size: 0x20,
+                align: 8,
+                tls: 0,
+                tp: 0x10,
+            }
+        );
+        assert_eq!(layout.tp - layout.tls, 16);
+
+        let mut block = [0xaa; 0x20];
+        let tp = template.init_block(&layout, &mut block, 0x1000).unwrap();
+        assert_eq!(tp, 0x1010);
+        assert_eq!(
+            block[..0x10],
+            [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(block[0x10..0x18], 0x1010u64.to_le_bytes());
+    }
+
+    #[test]
+    fn layout_small_align() {
+        let template = TlsTemplate {
+            image: &[],
+            size: 12,
+            align: 4,
+        };
+        let layout = template.layout(8);
+        assert_eq!(layout.tp, 16);
+        assert_eq!(layout.tls, 4);
+        assert_eq!(layout.size, 24);
+    }
+}
@@ -0,0 +1,303 @@
+use super::error::WriteError;
+use super::fmt::*;
+use super::note::Note;
+use super::{bytes_of, ELF_MAGIC, EV_CURRENT};
+use core::mem::size_of;
+
+/// Where a `Writer` puts the image, e.g. a buffer or a disk.
+pub trait Output {
+    /// Write `data` at the offset `off` of the image.
+    fn write_at(&mut self, off: u64, data: &[u8]) -> Result<(), WriteError>;
+}
+
+impl Output for &mut [u8] {
+    fn write_at(&mut self, off: u64, data: &[u8]) -> Result<(), WriteError> {
+        let end = off.saturating_add(data.len() as u64);
+        match self.get_mut(off as usize..end as usize) {
+            Some(dst) if end <= usize::MAX as u64 => {
+                dst.copy_from_slice(data);
+                Ok(())
+            }
+            _ => Err(WriteError::NoSpace(end)),
+        }
+    }
+}
+
+const PAGE_SIZE: u64 = 0x1000;
+// Alignment of the note segments.
+const NOTE_ALIGN: u64 = 4;
+
+/// Emits an x86_64 ELF64 image of segments, such as a core dump (CORE) or
+/// an executable (EXEC).
+///
+/// The ELF header and the program header table, sized for a fixed number of
+/// segments, come first. The contents of the segments follow in the order
+/// they are added. The image has no section header table.
+#[derive(Debug)]
+pub struct Writer<O: Output> {
+    out: O,
+    etype: EType,
+    entry: u64,
+    // Number of the segments added, and room for them in the table.
+    phnum: u16,
+    phcap: u16,
+    // End of the image written so far.
+    cursor: u64,
+}
+
+impl<O: Output> Writer<O> {
+    /// Start an image of `etype` holding up to `phcap` segments.
+    pub fn new(out: O, etype: EType, phcap: u16) -> Self {
+        Writer {
+            out,
+            etype,
+            entry: 0,
+            phnum: 0,
+            phcap,
+            cursor: Self::phdr_at(phcap),
+        }
+    }
+
+    pub fn set_entry(&mut self, entry: u64) {
+        self.entry = entry;
+    }
+
+    // Zero-fill the image from `start` up to `end`.
+    fn zero(&mut self, mut start: u64, end: u64) -> Result<(), WriteError> {
+        const ZEROS: [u8; 64] = [0; 64];
+        while start < end {
+            let len = (end - start).min(ZEROS.len() as u64);
+            self.out.write_at(start, &ZEROS[..len as usize])?;
+            start += len;
+        }
+        Ok(())
+    }
+
+    // Zero-fill the image up to `off`.
+    fn pad_to(&mut self, off: u64) -> Result<(), WriteError> {
+        self.zero(self.cursor, off)?;
+        self.cursor = self.cursor.max(off);
+        Ok(())
+    }
+
+    // Offset of the `index`th entry of the program header table.
+    const fn phdr_at(index: u16) -> u64 {
+        size_of::<ELFHeader64>() as u64
+            + index as u64 * size_of::<ProgHeader64>() as u64
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<(), WriteError> {
+        self.out.write_at(self.cursor, data)?;
+        self.cursor += data.len() as u64;
+        Ok(())
+    }
+
+    // Check that there is room for another segment, whose contents end at
+    // `end`, before any of it is written.
+    fn reserve(&mut self, end: u64) -> Result<(), WriteError> {
+        if self.phnum == self.phcap {
+            return Err(WriteError::TooManySegments);
+        }
+        if end > self.cursor {
+            self.out.write_at(end - 1, &[0])?;
+        }
+        Ok(())
+    }
+
+    fn push_phdr(&mut self, phdr: ProgHeader64) -> Result<(), WriteError> {
+        if self.phnum == self.phcap {
+            return Err(WriteError::TooManySegments);
+        }
+        self.out
+            .write_at(Self::phdr_at(self.phnum), bytes_of(&phdr))?;
+        self.phnum += 1;
+        Ok(())
+    }
+
+    /// Add a LOAD segment mapping `data` at `vaddr`. The rest of the segment
+    /// up to `memsz` is zero-filled in memory, as the BSS is.
+    pub fn load(
+        &mut self,
+        vaddr: u64,
+        flags: PFlags,
+        data: &[u8],
+        memsz: u64,
+    ) -> Result<(), WriteError> {
+        // The offset must be congruent to the address modulo the page size.
+        let page = (self.cursor + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let off = page + vaddr % PAGE_SIZE;
+        self.reserve(off + data.len() as u64)?;
+        self.pad_to(off)?;
+        self.append(data)?;
+        self.push_phdr(ProgHeader64 {
+            p_type: PType::LOAD as u32,
+            p_flags: flags.bits(),
+            p_offset: off,
+            p_vaddr: vaddr,
+            p_paddr: 0,
+            p_filesz: data.len() as u64,
+            p_memsz: memsz.max(data.len() as u64),
+            p_align: PAGE_SIZE,
+        })
+    }
+
+    /// Add a NOTE segment holding `notes`, e.g. the NT_PRSTATUS notes of
+    /// the threads of a core dump.
+    pub fn notes(&mut self, notes: &[Note<'_>]) -> Result<(), WriteError> {
+        let align = |v: u64| (v + NOTE_ALIGN - 1) & !(NOTE_ALIGN - 1);
+        let size = notes.iter().fold(0, |size, note| {
+            size + size_of::<NoteHeader>() as u64
+                + align(note.name.len() as u64 + 1)
+                + align(note.desc.len() as u64)
+        });
+        self.reserve(align(self.cursor) + size)?;
+        self.pad_to(align(self.cursor))?;
+        let off = self.cursor;
+        for note in notes {
+            let hdr = NoteHeader {
+                n_namesz: note.name.len() as u32 + 1,
+                n_descsz: note.desc.len() as u32,
+                n_type: note.ntype,
+            };
+            self.append(bytes_of(&hdr))?;
+            self.append(note.name)?;
+            self.append(&[0])?;
+            self.pad_to(align(self.cursor))?;
+            self.append(note.desc)?;
+            self.pad_to(align(self.cursor))?;
+        }
+        self.push_phdr(ProgHeader64 {
+            p_type: PType::NOTE as u32,
+            p_flags: 0,
+            p_offset: off,
+            p_vaddr: 0,
+            p_paddr: 0,
+            p_filesz: self.cursor - off,
+            p_memsz: 0,
+            p_align: NOTE_ALIGN,
+        })
+    }
+
+    /// Write the ELF header, and return the output along with the size of
+    /// the image.
+    pub fn finish(mut self) -> Result<(O, u64), WriteError> {
+        let hdr = ELFHeader64 {
+            ei_magic: ELF_MAGIC,
+            ei_class: EIClass::Bit64 as u8,
+            ei_data: EIData::LEndian as u8,
+            ei_version: EV_CURRENT as u8,
+            ei_osabi: OSabi::SystemV as u8,
+            ei_abiversion: 0,
+            ei_pad: [0; 7],
+            e_type: self.etype as u16,
+            e_machine: EMachine::X8664 as u16,
+            e_version: EV_CURRENT,
+            e_entry: self.entry,
+            e_phoff: size_of::<ELFHeader64>() as u64,
+            e_shoff: 0,
+            e_flags: 0,
+            e_ehsize: size_of::<ELFHeader64>() as u16,
+            e_phentsize: size_of::<ProgHeader64>() as u16,
+            e_phnum: self.phnum,
+            e_shentsize: 0,
+            e_shnum: 0,
+            e_shstrndx: 0,
+        };
+        self.out.write_at(0, bytes_of(&hdr))?;
+        // Leave no garbage in the unused entries of the table.
+        self.zero(Self::phdr_at(self.phnum), Self::phdr_at(self.phcap))?;
+        Ok((self.out, self.cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::NT_PRSTATUS;
+    use crate::ELF;
+
+    #[test]
+    fn core_round_trip() {
+        let mut buf = vec![0xaa; 0x4000];
+        let mut writer = Writer::new(&mut buf[..], EType::CORE, 3);
+        let notes = [
+            Note {
+                name: b"CORE",
+                ntype: NT_PRSTATUS,
+                desc: &[1, 2, 3, 4, 5],
+            },
+            Note {
+                name: b"GNU",
+                ntype: 3,
+                desc: &[6; 8],
+            },
+        ];
+        writer.notes(&notes).unwrap();
+        writer
+            .load(0x401010, PFlags::R | PFlags::W, &[0x55; 0x20], 0x100)
+            .unwrap();
+        let (_, size) = writer.finish().unwrap();
+
+        let elf = ELF::parse(&buf[..size as usize]).unwrap();
+        assert_eq!(elf.header().e_type, EType::CORE as u16);
+        let phdrs = elf.phdrs().collect::<Vec<_>>();
+        assert_eq!(phdrs.len(), 2);
+
+        let read = elf.segment_notes(&phdrs[0]).unwrap().collect::<Vec<_>>();
+        assert_eq!(read.len(), notes.len());
+        for (read, note) in read.iter().zip(notes.iter()) {
+            assert_eq!(read.name, note.name);
+            assert_eq!(read.ntype, note.ntype);
+            assert_eq!(read.desc, note.desc);
+        }
+
+        let load = &phdrs[1];
+        assert!(load.is_load());
+        assert_eq!(load.p_vaddr, 0x401010);
+        assert_eq!(load.p_offset % PAGE_SIZE, 0x10);
+        assert_eq!(load.p_filesz, 0x20);
+        assert_eq!(load.p_memsz, 0x100);
+        assert_eq!(elf.data_at(0x401010, 0x20), Some(&[0x55; 0x20][..]));
+        // The unused entry of the table is zeroed.
+        let unused = Writer::<&mut [u8]>::phdr_at(2) as usize;
+        assert!(buf[unused..unused + size_of::<ProgHeader64>()]
+            .iter()
+            .all(|&b| b == 0));
+    }
+
+    #[test]
+    fn notes_without_room() {
+        let note = Note {
+            name: b"CORE",
+            ntype: NT_PRSTATUS,
+            desc: &[1; 0x80],
+        };
+        let mut buf = [0xaa; 0x200];
+        let mut writer = Writer::new(&mut buf[..], EType::CORE, 2);
+        writer.notes(&[note]).unwrap();
+        let end = writer.cursor as usize;
+        assert_eq!(end, 0xb0 + 0x94);
+        assert!(matches!(
+            writer.notes(&[note, note]),
+            Err(WriteError::NoSpace(_))
+        ));
+        // Nothing of the notes was written.
+        assert_eq!(writer.cursor as usize, end);
+        assert!(buf[end..].iter().all(|&b| b == 0xaa));
+    }
+
+    #[test]
+    fn write_errors() {
+        let mut buf = [0; 0x200];
+        let mut writer = Writer::new(&mut buf[..], EType::EXEC, 1);
+        writer.notes(&[]).unwrap();
+        assert_eq!(writer.notes(&[]), Err(WriteError::TooManySegments));
+
+        let mut buf = [0; 0x200];
+        let mut writer = Writer::new(&mut buf[..], EType::EXEC, 1);
+        assert!(matches!(
+            writer.load(0x1000, PFlags::R, &[0; 0x10], 0x10),
+            Err(WriteError::NoSpace(_))
+        ));
+    }
+}
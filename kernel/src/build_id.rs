@@ -0,0 +1,18 @@
+use elf::note::{BuildId, NoteIter};
+
+extern "C" {
+    static __build_id_start: u8;
+    static __build_id_end: u8;
+}
+
+/// The GNU build-id of the running kernel, which matches the build-id of
+/// build/kern.bin that it was booted from.
+pub fn build_id() -> Option<BuildId<'static>> {
+    unsafe {
+        let start = &__build_id_start as *const u8;
+        let end = &__build_id_end as *const u8;
+        let notes =
+            core::slice::from_raw_parts(start, end as usize - start as usize);
+        NoteIter::new(notes, 4).build_id()
+    }
+}
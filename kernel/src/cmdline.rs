@@ -0,0 +1,182 @@
+//! The kernel command line.
+//!
+//! The command line is a list of `key=value` options separated by spaces.
+//! The recognized options are
+//!   loglevel=<error|warn|info|debug|0-3>
+//!   console=<serial|cga|lpt>[,...], in addition to the serial console
+//!   mem=<size>[K|M|G]
+//!   smp=<cpus>
+//!   test=<name>
+//!   nokaslr
+
+/// The verbosity of the kernel messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl LogLevel {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "error" | "0" => Some(LogLevel::Error),
+            "warn" | "1" => Some(LogLevel::Warn),
+            "info" | "2" => Some(LogLevel::Info),
+            "debug" | "3" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// The devices that the console writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Consoles {
+    pub serial: bool,
+    pub cga: bool,
+    pub lpt: bool,
+}
+
+impl Consoles {
+    pub const ALL: Consoles = Consoles {
+        serial: true,
+        cga: true,
+        lpt: true,
+    };
+
+    // The serial console stays on, as it is the one that `make run` shows,
+    // and the listed ones are added to it.
+    fn parse(s: &str) -> Option<Self> {
+        let mut consoles = Consoles {
+            serial: true,
+            cga: false,
+            lpt: false,
+        };
+        for name in s.split(',') {
+            match name {
+                "serial" | "ttyS0" => consoles.serial = true,
+                "cga" | "vga" => consoles.cga = true,
+                "lpt" => consoles.lpt = true,
+                _ => return None,
+            }
+        }
+        Some(consoles)
+    }
+}
+
+/// The options given in the kernel command line.
+// XXX: smp and test have no users until mp and the tests land.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub loglevel: LogLevel,
+    pub console: Consoles,
+    /// The upper limit of the physical memory to use.
+    pub mem: Option<u64>,
+    /// The number of the cpus to bring up.
+    pub smp: Option<u32>,
+    /// The test to run instead of the normal boot.
+    pub test: Option<&'static str>,
+    /// Whether the bootloader may load the kernel at a random address. The
+    /// bootloader reads the option itself; the kernel only checks that it
+    /// was followed.
+    pub kaslr: bool,
+}
+
+impl Options {
+    pub const fn new() -> Self {
+        Options {
+            loglevel: LogLevel::Info,
+            console: Consoles::ALL,
+            mem: None,
+            smp: None,
+            test: None,
+            kaslr: true,
+        }
+    }
+
+    /// Parse the command line. The malformed or unknown options are
+    /// reported and ignored.
+    pub fn parse(cmdline: &'static str) -> Self {
+        let mut options = Options::new();
+        for option in cmdline.split_ascii_whitespace() {
+            let (key, value) = match option.find('=') {
+                Some(i) => (&option[..i], &option[i + 1..]),
+                None => (option, ""),
+            };
+            let ok = match key {
+                "loglevel" => LogLevel::parse(value)
+                    .map(|level| options.loglevel = level)
+                    .is_some(),
+                "console" => Consoles::parse(value)
+                    .map(|console| options.console = console)
+                    .is_some(),
+                "mem" => parse_size(value)
+                    .map(|mem| options.mem = Some(mem))
+                    .is_some(),
+                "smp" => match parse_u64(value) {
+                    Some(cpus) if cpus > 0 && cpus <= u32::MAX as u64 => {
+                        options.smp = Some(cpus as u32);
+                        true
+                    }
+                    _ => false,
+                },
+                "nokaslr" if value.is_empty() => {
+                    options.kaslr = false;
+                    true
+                }
+                "test" if !value.is_empty() => {
+                    options.test = Some(value);
+                    true
+                }
+                _ => false,
+            };
+            if !ok {
+                crate::log!(
+                    Warn,
+                    "cmdline: ignoring bad option \"{}\"",
+                    option
+                );
+            }
+        }
+        options
+    }
+}
+
+// A decimal or 0x-prefixed hexadecimal number.
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// A number with an optional K, M or G suffix.
+fn parse_size(s: &str) -> Option<u64> {
+    let (num, shift) = match s.as_bytes().last()? {
+        b'k' | b'K' => (&s[..s.len() - 1], 10),
+        b'm' | b'M' => (&s[..s.len() - 1], 20),
+        b'g' | b'G' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    parse_u64(num)?.checked_mul(1 << shift)
+}
+
+static mut OPTIONS: Options = Options::new();
+
+#[link_section = ".init.text"]
+pub fn init(cmdline: Option<&'static str>) {
+    if let Some(cmdline) = cmdline {
+        crate::log!(Info, "Command line: {}", cmdline);
+        unsafe {
+            OPTIONS = Options::parse(cmdline);
+        }
+    }
+}
+
+/// The options of the kernel command line.
+pub fn options() -> &'static Options {
+    unsafe { &OPTIONS }
+}
@@ -2,6 +2,7 @@ mod cga; // Color Graphics Adapter
 mod lpt; // Line Print Terminal
 mod serial; // Serial I/O
 
+use crate::cmdline::Consoles;
 use crate::locking::SpinLock;
 use arch::Virtual;
 use cga::CGA;
@@ -12,6 +13,7 @@ use serial::Serial;
 struct Console {
     ser: bool,
     cga: CGA,
+    outputs: Consoles,
 }
 
 impl Console {
@@ -19,19 +21,25 @@ impl Console {
         Console {
             ser: Serial::init().is_ok(),
             cga: CGA::init(kern_base),
+            outputs: Consoles::ALL,
         }
     }
 
     fn puts(&mut self, s: &str) {
         for byte in s.chars() {
             // FIXME: fix CGA later
-            self.cga.putc(cga::VGAChar {
-                ascii: byte as u8,
-                color: Default::default(),
-            });
-            LPT::putc(byte as u8);
-            Serial::putc(byte as u8);
-            if self.ser {}
+            if self.outputs.cga {
+                self.cga.putc(cga::VGAChar {
+                    ascii: byte as u8,
+                    color: Default::default(),
+                });
+            }
+            if self.outputs.lpt {
+                LPT::putc(byte as u8);
+            }
+            if self.outputs.serial && self.ser {
+                Serial::putc(byte as u8);
+            }
         }
     }
 }
@@ -53,6 +61,14 @@ pub fn init(kern_base: Virtual) {
     }
 }
 
+/// Select the devices that the console writes to.
+#[link_section = ".init.text"]
+pub fn select(outputs: Consoles) {
+    unsafe {
+        CONSOLE.as_mut().unwrap().borrow().outputs = outputs;
+    }
+}
+
 /// Print with lock.
 #[macro_export]
 macro_rules! print {
@@ -65,6 +81,19 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+/// Print a line of the LogLevel `$level`, if the loglevel of the command
+/// line lets it through.
+#[macro_export]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => (
+        if $crate::cmdline::options().loglevel
+            >= $crate::cmdline::LogLevel::$level
+        {
+            $crate::println!($($arg)*);
+        }
+    );
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
@@ -1,30 +1,66 @@
+use crate::multiboot2::{self, MULTIBOOT2_MAGIC};
 use arch::Virtual;
+use bootinfo::{BootInfo, BOOTINFO_MAGIC};
 
-extern "C" {
-    static _edata: u64;
-    static _end: u64;
-}
-
-// To ensure that all static/global variables are zero,
-// the kernel zeros out the bss section first.
+// Find the boot information that the loader passed in `magic` and `info`,
+// the physical address of it. `magic` tells which loader it was.
 #[link_section = ".init.text"]
-fn __cleanup_bss() {
-    unsafe {
-        let edata = &_edata as *const _ as u64;
-        let end = &_end as *const _ as u64;
-        core::intrinsics::write_bytes(
-            edata as *mut u8,
-            0,
-            (end - edata) as usize,
-        );
+unsafe fn boot_info(
+    kern_base: &Virtual,
+    magic: u64,
+    info: u64,
+) -> Option<&'static BootInfo> {
+    match magic {
+        BOOTINFO_MAGIC => ((kern_base.to_u64() + info) as *const BootInfo)
+            .as_ref()
+            .filter(|boot_info| boot_info.is_valid()),
+        MULTIBOOT2_MAGIC => Some(multiboot2::boot_info(kern_base, info)),
+        _ => None,
     }
 }
 
 #[link_section = ".init.text"]
-pub fn init() {
-    __cleanup_bss();
-    crate::dev::tty::init(Virtual::new(0x8004000000).unwrap());
-    crate::mm::init(Virtual::new(0x8004000000).unwrap());
+pub fn init(magic: u64, info: u64) {
+    let kern_base = Virtual::new(0x8000000000).unwrap();
+    crate::dev::tty::init(kern_base);
+    if let Some(id) = crate::build_id::build_id() {
+        crate::log!(Info, "rOS build-id {}", id);
+    }
+    let boot_info = match unsafe { boot_info(&kern_base, magic, info) } {
+        Some(boot_info) => boot_info,
+        None => panic!("Unknown boot protocol (magic 0x{:x})", magic),
+    };
+    crate::cmdline::init(unsafe { boot_info.cmdline(kern_base.to_u64()) });
+    crate::dev::tty::select(crate::cmdline::options().console);
+    for module in unsafe { boot_info.modules(kern_base.to_u64()) } {
+        crate::log!(
+            Info,
+            "Module 0x{:x}-0x{:x} {}",
+            module.range.start,
+            module.range.end,
+            unsafe { module.cmdline(kern_base.to_u64()) }.unwrap_or("")
+        );
+    }
+    if boot_info.kernel_slide != 0 {
+        crate::log!(Info, "KASLR: slide 0x{:x}", boot_info.kernel_slide);
+        if !crate::cmdline::options().kaslr {
+            crate::log!(Warn, "KASLR: the loader ignored nokaslr");
+        }
+    }
+    let fb = &boot_info.framebuffer;
+    if fb.addr != 0 {
+        crate::log!(
+            Info,
+            "Framebuffer {}x{}x{} at 0x{:x}, pitch {}",
+            fb.width,
+            fb.height,
+            fb.bpp,
+            fb.addr,
+            fb.pitch
+        );
+    }
+    crate::symbols::init(&kern_base, boot_info);
+    crate::mm::init(kern_base, boot_info);
     unimplemented!();
     // TODO: mm
     // TODO: mp
@@ -1,5 +1,9 @@
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     crate::println_unlocked!("{}", info);
+    if let Some(id) = crate::build_id::build_id() {
+        crate::println_unlocked!("build-id {}", id);
+    }
+    crate::symbols::backtrace();
     loop {}
 }
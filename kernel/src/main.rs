@@ -1,17 +1,27 @@
 #![no_std]
 #![no_main]
-#![feature(asm, const_raw_ptr_deref, const_if_match, core_intrinsics)]
+#![feature(
+    asm,
+    global_asm,
+    const_raw_ptr_deref,
+    const_if_match,
+    core_intrinsics
+)]
 
 #[macro_use]
 extern crate arch;
+mod build_id;
+mod cmdline;
 mod dev;
 mod initializer;
 mod lang;
 mod locking;
 mod mm;
+mod multiboot2;
+mod symbols;
 
 #[no_mangle]
-unsafe extern "C" fn main() -> ! {
-    initializer::init();
+unsafe extern "C" fn main(magic: u64, info: u64) -> ! {
+    initializer::init(magic, info);
     ::core::hint::unreachable_unchecked()
 }
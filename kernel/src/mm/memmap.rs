@@ -0,0 +1,137 @@
+use super::region::{MemoryRegion, Region, RegionType};
+use crate::cmdline;
+use arch::Virtual;
+use bootinfo::*;
+
+impl From<&MemoryEntry> for Region {
+    #[link_section = ".init.text"]
+    fn from(mm: &MemoryEntry) -> Region {
+        Region {
+            addr: mm.addr,
+            len: mm.len,
+            mtype: match mm.kind {
+                MEMORY_USABLE => RegionType::Usable,
+                MEMORY_ACPI_RECLAIMABLE => RegionType::AcpiReclaimable,
+                MEMORY_ACPI_NVS => RegionType::AcpiNvs,
+                MEMORY_BAD => RegionType::BadMemory,
+                _ => RegionType::Reserved,
+            },
+        }
+    }
+}
+
+// The bootloader enters the kernel on the stack that grows down from
+// BOOT_STACK_TOP, and the kernel keeps running on it.
+const BOOT_STACK_TOP: u64 = 0x200000;
+const BOOT_STACK_BOTTOM: u64 = 0x100000;
+
+#[link_section = ".init.text"]
+fn source_name(source: u32) -> &'static str {
+    match source {
+        MMAP_SOURCE_E820 => "e820",
+        MMAP_SOURCE_E801 => "e801",
+        MMAP_SOURCE_LEGACY => "int 0x12 and 0x88",
+        MMAP_SOURCE_MULTIBOOT2 => "multiboot2",
+        _ => "nowhere",
+    }
+}
+
+// Refuse the maps that the mm can't be built on: an empty one, one without
+// the memory above 1 MiB to load the kernel in, and one that is garbage.
+#[link_section = ".init.text"]
+fn check_memory_map(map: &[MemoryEntry]) -> Result<(), &'static str> {
+    if map.is_empty() {
+        return Err("no entries");
+    }
+    let mut usable = 0;
+    for (i, entry) in map.iter().enumerate() {
+        let end = entry
+            .addr
+            .checked_add(entry.len)
+            .ok_or("an entry wraps around")?;
+        if entry.kind == 0 {
+            return Err("an entry has no type");
+        }
+        if entry.kind != MEMORY_USABLE {
+            continue;
+        }
+        let overlaps = map[i + 1..].iter().any(|other| {
+            other.kind == MEMORY_USABLE
+                && other.addr < end
+                && entry.addr < other.addr.saturating_add(other.len)
+        });
+        if overlaps {
+            return Err("usable entries overlap");
+        }
+        if end > 0x100000 {
+            usable += end - entry.addr.max(0x100000);
+        }
+    }
+    if usable == 0 {
+        return Err("no usable memory above 1 MiB");
+    }
+    Ok(())
+}
+
+#[link_section = ".init.text"]
+pub fn read_memory_map(
+    kern_base: &Virtual,
+    boot_info: &BootInfo,
+) -> MemoryRegion {
+    let base = kern_base.to_u64();
+    let options = cmdline::options();
+    // mem= hides the memory above the limit.
+    let limit = options.mem.unwrap_or(u64::MAX);
+    let map = unsafe { boot_info.memory_map(base) };
+    let source = source_name(boot_info.mmap_source);
+    if let Err(e) = check_memory_map(map) {
+        panic!("Bad memory map from {}: {}", source, e);
+    }
+    crate::log!(Info, "Memory map from {}", source);
+    let mut regions = MemoryRegion::new();
+    map.iter()
+        .map(Region::from)
+        .filter(|region| region.addr < limit)
+        .for_each(|region| {
+            regions.add(Region {
+                len: region.len.min(limit - region.addr),
+                ..region
+            })
+        });
+    // Keep what the loader left for the kernel.
+    regions.reserve(
+        boot_info.kernel.start,
+        boot_info.kernel.len(),
+        RegionType::Kernel,
+    );
+    regions.reserve(
+        boot_info.bootloader.start,
+        boot_info.bootloader.len(),
+        RegionType::Bootloader,
+    );
+    regions.reserve(
+        boot_info.staging.start,
+        boot_info.staging.len(),
+        RegionType::BootInfo,
+    );
+    regions.reserve(
+        BOOT_STACK_BOTTOM,
+        BOOT_STACK_TOP - BOOT_STACK_BOTTOM,
+        RegionType::KernelStack,
+    );
+    regions.reserve(
+        boot_info.page_tables.start,
+        boot_info.page_tables.len(),
+        RegionType::PageTable,
+    );
+    for module in unsafe { boot_info.modules(base) } {
+        regions.reserve(
+            module.range.start,
+            module.range.len(),
+            RegionType::Package,
+        );
+    }
+    crate::log!(Debug, "{}", regions);
+    regions.show_info();
+    regions
+}
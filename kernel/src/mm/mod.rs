@@ -1,8 +1,9 @@
-mod multiboot;
+mod memmap;
 mod region;
 mod zone;
 
 use arch::Virtual;
+use bootinfo::BootInfo;
 
 extern "C" {
     static _end: u64;
@@ -26,7 +27,7 @@ fn early_boot_alloc<T>(n: u64) -> Result<&'static mut T, ()> {
 }
 
 #[link_section = ".init.text"]
-pub fn init(kern_base: Virtual) {
+pub fn init(kern_base: Virtual, boot_info: &BootInfo) {
     unsafe {
         match Virtual::new(page_up!(&_end as *const _ as u64)) {
             Ok(virt) => NEXT_FREE = Some(virt),
@@ -34,7 +35,7 @@ pub fn init(kern_base: Virtual) {
         }
     }
 
-    multiboot::read_mb_info(&kern_base)
+    memmap::read_memory_map(&kern_base, boot_info)
         .iter_usable()
         .for_each(zone::foster_zone);
     // XXX: The allocator claim memory from the zone.
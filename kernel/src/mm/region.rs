@@ -112,22 +112,36 @@ impl MemoryRegion {
     }
 
     pub fn show_info(&self) {
-        crate::println!(
+        crate::log!(
+            Info,
             "Memory Available: {}MB",
             self.total_size / 1024 / 1024
         );
-        crate::println!(
+        crate::log!(
+            Info,
             "Total {} pages available.",
             self.total_size / arch::PAGE_SIZE
         );
     }
 
+    // Whether there is an entry left for `d`. The map is left without `d`
+    // if there is none, as the early kernel has no place to put more.
+    fn has_room_for(&self, d: &Region) -> bool {
+        if self.index < self.regions.len() {
+            return true;
+        }
+        crate::log!(Warn, "Too many memory regions, dropping {}", d);
+        false
+    }
+
     pub fn add(&mut self, d: Region) {
-        self.update_meta(&d);
         for i in 0..self.index {
             if self.regions[i].addr > d.addr {
                 // Now we find index to insert
                 if self.try_merge_at(i, d).is_err() {
+                    if !self.has_room_for(&d) {
+                        return;
+                    }
                     for j in 0..(self.index - i) {
                         self.regions[self.index - j] =
                             self.regions[self.index - j - 1];
@@ -135,15 +149,55 @@ impl MemoryRegion {
                     self.regions[i] = d;
                     self.index += 1;
                 }
+                self.update_meta(&d);
                 return;
             }
         }
 
         // forward merge
         if self.index == 0 || self.try_merge_at(self.index - 1, d).is_err() {
+            if !self.has_room_for(&d) {
+                return;
+            }
             self.regions[self.index] = d;
             self.index += 1;
         }
+        self.update_meta(&d);
+    }
+
+    /// Carve `[addr, addr + len)` out of the usable regions as `mtype`.
+    pub fn reserve(&mut self, addr: u64, len: u64, mtype: RegionType) {
+        if len == 0 {
+            return;
+        }
+        let start = page_down!(addr);
+        let end = page_up!(addr + len);
+        let old = core::mem::replace(self, MemoryRegion::new());
+        for region in old.regions[..old.index].iter() {
+            let (lo, hi) = (region.addr, region.next_addr());
+            if region.mtype != RegionType::Usable || hi <= start || end <= lo {
+                self.add(*region);
+                continue;
+            }
+            if lo < start {
+                self.add(Region {
+                    len: start - lo,
+                    ..*region
+                });
+            }
+            self.add(Region {
+                addr: lo.max(start),
+                len: hi.min(end) - lo.max(start),
+                mtype,
+            });
+            if end < hi {
+                self.add(Region {
+                    addr: end,
+                    len: hi - end,
+                    ..*region
+                });
+            }
+        }
     }
 
     pub fn iter_usable(&self) -> RegionIter {
@@ -87,7 +87,7 @@ impl Zone {
     }
 
     pub fn push_region(&mut self, region: Region) {
-        crate::println!("Push Zone: {}", region);
+        crate::log!(Debug, "Push Zone: {}", region);
         if self.is_initialized() {
             self.start_pfn = pfn!(region.addr);
         }
@@ -0,0 +1,211 @@
+//! Boot from the Multiboot2 loaders, such as GRUB.
+//!
+//! multiboot2.s brings the kernel to the long mode with the page tables of
+//! bootstrap.s, and this translates the Multiboot2 information into the
+//! `BootInfo` that the bootloader would have passed.
+
+use arch::Virtual;
+use bootinfo::*;
+use core::mem::size_of;
+use core::ptr;
+
+/// Passed in rdi by multiboot2.s.
+pub const MULTIBOOT2_MAGIC: u64 = 0x36d76289;
+
+global_asm!(include_str!("multiboot2.s"));
+global_asm!(concat!(
+    ".set BOOTSTRAP_OFFSET, 0x8000000000\n",
+    ".set bootstrap_long_entry, mb2_long\n",
+    include_str!("../../bootloader/src/bootstrap.s")
+));
+
+extern "C" {
+    static _text: u64;
+    static _end: u64;
+}
+
+// Types of the tags.
+const TAG_END: u32 = 0;
+const TAG_CMDLINE: u32 = 1;
+const TAG_MODULE: u32 = 3;
+const TAG_BOOTDEV: u32 = 5;
+const TAG_MMAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ELF_SECTIONS: u32 = 9;
+
+// FramebufferTag.fb_type of the direct color.
+const FRAMEBUFFER_RGB: u8 = 1;
+
+const MMAP_MAX: usize = 64;
+const MODULES_MAX: usize = 16;
+
+#[repr(C)]
+struct Tag {
+    kind: u32,
+    size: u32,
+}
+
+#[repr(C)]
+struct ModuleTag {
+    tag: Tag,
+    mod_start: u32,
+    mod_end: u32,
+    // Followed by the NUL-terminated command line.
+}
+
+#[repr(C)]
+struct MmapTag {
+    tag: Tag,
+    entry_size: u32,
+    entry_version: u32,
+    // Followed by the entries, which are laid out as MemoryEntry.
+}
+
+#[repr(C, packed)]
+struct FramebufferTag {
+    tag: Tag,
+    addr: u64,
+    pitch: u32,
+    width: u32,
+    height: u32,
+    bpp: u8,
+    fb_type: u8,
+    _reserved: u16,
+    // The color info of FRAMEBUFFER_RGB.
+    red_pos: u8,
+    red_size: u8,
+    green_pos: u8,
+    green_size: u8,
+    blue_pos: u8,
+    blue_size: u8,
+}
+
+#[repr(C)]
+struct ElfSectionsTag {
+    tag: Tag,
+    num: u32,
+    entsize: u32,
+    shndx: u32,
+    // Followed by the section headers.
+}
+
+static mut BOOT_INFO: BootInfo = BootInfo::new();
+static mut MEMORY_MAP: [MemoryEntry; MMAP_MAX] = [MemoryEntry {
+    addr: 0,
+    len: 0,
+    kind: 0,
+    _reserved: 0,
+}; MMAP_MAX];
+static mut MODULES: [Module; MODULES_MAX] = [Module {
+    range: PhysRange::new(0, 0),
+    cmdline: 0,
+    _reserved: 0,
+}; MODULES_MAX];
+
+// Length of the NUL-terminated string at `s`.
+unsafe fn strlen(s: *const u8) -> usize {
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
+    }
+    len
+}
+
+/// Translate the Multiboot2 information at the physical address `info`.
+#[link_section = ".init.text"]
+pub unsafe fn boot_info(kern_base: &Virtual, info: u64) -> &'static BootInfo {
+    let base = kern_base.to_u64();
+    let boot_info = &mut BOOT_INFO;
+    boot_info.kernel = PhysRange::new(
+        &_text as *const _ as u64 - base,
+        &_end as *const _ as u64 - base,
+    );
+
+    let total_size = ptr::read((base + info) as *const u32) as u64;
+    // The strings that the boot information points to stay in the GRUB's.
+    boot_info.bootloader = PhysRange::new(info, info + total_size);
+    // The tags follow the total size and a reserved field, aligned to 8.
+    let mut off = 8;
+    while off + size_of::<Tag>() as u64 <= total_size {
+        let pa = info + off;
+        let tag = &*((base + pa) as *const Tag);
+        let data = pa + size_of::<Tag>() as u64;
+        if (tag.size as usize) < size_of::<Tag>() {
+            break;
+        }
+        match tag.kind {
+            TAG_END => break,
+            TAG_CMDLINE => {
+                boot_info.cmdline_addr = data;
+                boot_info.cmdline_len =
+                    strlen((base + data) as *const u8) as u32;
+            }
+            TAG_MODULE if (boot_info.mods_count as usize) < MODULES_MAX => {
+                let module = &*((base + pa) as *const ModuleTag);
+                MODULES[boot_info.mods_count as usize] = Module {
+                    range: PhysRange::new(
+                        module.mod_start as u64,
+                        module.mod_end as u64,
+                    ),
+                    cmdline: pa + size_of::<ModuleTag>() as u64,
+                    _reserved: 0,
+                };
+                boot_info.mods_count += 1;
+                boot_info.mods_addr = MODULES.as_ptr() as u64 - base;
+            }
+            TAG_BOOTDEV => {
+                boot_info.boot_drive = ptr::read((base + data) as *const u32);
+            }
+            TAG_MMAP => {
+                let mmap = &*((base + pa) as *const MmapTag);
+                let entries = pa + size_of::<MmapTag>() as u64;
+                let mut count = 0;
+                let mut entry = entries;
+                while entry + mmap.entry_size as u64 <= pa + tag.size as u64
+                    && count < MMAP_MAX
+                    && mmap.entry_size as usize >= size_of::<MemoryEntry>()
+                {
+                    MEMORY_MAP[count] = ptr::read_unaligned(
+                        (base + entry) as *const MemoryEntry,
+                    );
+                    count += 1;
+                    entry += mmap.entry_size as u64;
+                }
+                boot_info.mmap_addr = MEMORY_MAP.as_ptr() as u64 - base;
+                boot_info.mmap_count = count as u32;
+                boot_info.mmap_source = MMAP_SOURCE_MULTIBOOT2;
+            }
+            TAG_FRAMEBUFFER
+                if tag.size as usize >= size_of::<FramebufferTag>() =>
+            {
+                let fb = &*((base + pa) as *const FramebufferTag);
+                if fb.fb_type == FRAMEBUFFER_RGB {
+                    boot_info.framebuffer = Framebuffer {
+                        addr: fb.addr,
+                        pitch: fb.pitch,
+                        width: fb.width,
+                        height: fb.height,
+                        bpp: fb.bpp,
+                        red_pos: fb.red_pos,
+                        red_size: fb.red_size,
+                        green_pos: fb.green_pos,
+                        green_size: fb.green_size,
+                        blue_pos: fb.blue_pos,
+                        blue_size: fb.blue_size,
+                        _reserved: 0,
+                    };
+                }
+            }
+            TAG_ELF_SECTIONS => {
+                let sections = &*((base + pa) as *const ElfSectionsTag);
+                boot_info.shdr_addr = pa + size_of::<ElfSectionsTag>() as u64;
+                boot_info.shdr_num = sections.num;
+                boot_info.shdr_size = sections.entsize;
+                boot_info.shdr_shndx = sections.shndx;
+            }
+            _ => {}
+        }
+        off = (off + tag.size as u64 + 7) & !7;
+    }
+    boot_info
+}
@@ -0,0 +1,69 @@
+# Entry from the Multiboot2 loaders, such as GRUB.
+.intel_syntax noprefix
+
+.set KERNBASE, 0x8000000000
+.set MB2_HEADER_MAGIC, 0xe85250d6
+.set MB2_BOOTLOADER_MAGIC, 0x36d76289
+
+.section .multiboot2, "a"
+.p2align 3
+mb2_header:
+  .long MB2_HEADER_MAGIC
+  .long 0                                # i386
+  .long mb2_header_end - mb2_header
+  .long 0x100000000 - (MB2_HEADER_MAGIC + (mb2_header_end - mb2_header))
+
+  # The ELF entry is a virtual address, so give the physical one.
+  .p2align 3
+mb2_entry_tag:
+  .short 3                               # entry address
+  .short 0
+  .long 12
+  .long mb2_entry - KERNBASE
+
+  .p2align 3
+mb2_end_tag:
+  .short 0
+  .short 0
+  .long 8
+mb2_header_end:
+
+.section .bootstraping, "awx"
+.code32
+# Runs at the physical address with the paging disabled, the magic in eax
+# and the physical address of the Multiboot2 information in ebx.
+mb2_entry:
+  cli
+  cmp eax, MB2_BOOTLOADER_MAGIC
+  jne mb2_fail
+  mov esp, offset mb2_stack_top - KERNBASE
+  mov [mb2_info - KERNBASE], ebx
+  jmp _head64
+
+mb2_fail:
+  hlt
+  jmp mb2_fail
+
+.code64
+# Runs at the physical address with the paging enabled, and jumps to main
+# with the magic in rdi and the physical address of the information in rsi.
+mb2_long:
+  mov ax, 0x10                           # DATA SEGMENT64
+  mov ds, ax
+  mov es, ax
+  mov ss, ax
+  movabs rsp, offset mb2_stack_top
+  xor rbp, rbp
+  mov edi, MB2_BOOTLOADER_MAGIC
+  movabs rax, offset mb2_info
+  mov esi, [rax]
+  movabs rax, offset main
+  jmp rax
+
+.section .bss
+.p2align 12
+mb2_stack:
+  .space 0x4000
+mb2_stack_top:
+mb2_info:
+  .long 0
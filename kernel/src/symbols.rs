@@ -0,0 +1,93 @@
+use arch::Virtual;
+use bootinfo::BootInfo;
+use core::mem::size_of;
+use core::{ptr, slice};
+use elf::fmt::{SHType, SectHeader64};
+use elf::section::StrTab;
+use elf::symbol::{Resolved, SymbolTable};
+
+// Upper bound of frames to walk, in case the frame chain is corrupted.
+const MAX_DEPTH: usize = 32;
+
+static mut KSYMS: Option<SymbolTable<'static>> = None;
+// How far the bootloader moved the kernel from the symbol values.
+static mut SLIDE: u64 = 0;
+
+/// Find the kernel symbol table from the ELF section headers that the
+/// bootloader loaded.
+#[link_section = ".init.text"]
+unsafe fn read_symbols(
+    kern_base: &Virtual,
+    boot_info: &BootInfo,
+) -> Option<SymbolTable<'static>> {
+    if boot_info.shdr_num == 0
+        || boot_info.shdr_size as usize != size_of::<SectHeader64>()
+    {
+        return None;
+    }
+    let shdrs =
+        (kern_base.to_u64() + boot_info.shdr_addr) as *const SectHeader64;
+    let shdr = |i: u32| {
+        if i < boot_info.shdr_num {
+            Some(ptr::read_unaligned(shdrs.add(i as usize)))
+        } else {
+            None
+        }
+    };
+    let contents = |shdr: &SectHeader64| {
+        slice::from_raw_parts(
+            (kern_base.to_u64() + shdr.sh_addr) as *const u8,
+            shdr.sh_size as usize,
+        )
+    };
+
+    let symtab = (0..boot_info.shdr_num)
+        .filter_map(shdr)
+        .find(|s| s.kind() == Some(SHType::SYMTAB) && s.sh_addr != 0)?;
+    let strtab = shdr(symtab.sh_link)
+        .filter(|s| s.kind() == Some(SHType::STRTAB) && s.sh_addr != 0)?;
+    Some(SymbolTable::new(
+        contents(&symtab),
+        StrTab::new(contents(&strtab)),
+    ))
+}
+
+#[link_section = ".init.text"]
+pub fn init(kern_base: &Virtual, boot_info: &BootInfo) {
+    unsafe {
+        KSYMS = read_symbols(kern_base, boot_info);
+        SLIDE = boot_info.kernel_slide;
+    }
+}
+
+/// Resolve `addr` to the kernel function that contains it.
+pub fn resolve(addr: u64) -> Option<Resolved<'static>> {
+    unsafe { KSYMS.as_ref()?.enclosing(addr.wrapping_sub(SLIDE)) }
+}
+
+/// Print the return addresses on the stack by following the frame pointers.
+/// The primary use of this is panic handler.
+pub fn backtrace() {
+    let mut rbp: u64;
+    unsafe {
+        asm!("mov %rbp, $0" : "=r"(rbp) : : : "volatile");
+    }
+    crate::println_unlocked!("Backtrace:");
+    for depth in 0..MAX_DEPTH {
+        if rbp == 0 || rbp & 0x7 != 0 {
+            break;
+        }
+        let frame = rbp as *const u64;
+        let ret = unsafe { *frame.add(1) };
+        if ret == 0 {
+            break;
+        }
+        match resolve(ret) {
+            Some(sym) => {
+                crate::println_unlocked!("  #{} 0x{:x} {}", depth, ret, sym)
+            }
+            None => crate::println_unlocked!("  #{} 0x{:x}", depth, ret),
+        }
+        rbp = unsafe { *frame };
+    }
+}
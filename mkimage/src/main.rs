@@ -0,0 +1,259 @@
+//! Builds the disk image from the bootloader, the kernel, the command line
+//! and the modules, as bootimg describes.
+
+use bootimg::*;
+use crypto::{ed25519, sha256};
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "usage: mkimage -o <image> [-c <cmdline>] [-k <key>] \
+                     [-v <width>x<height>x<bpp>] <bootloader> <kernel> \
+                     [<module>...]
+       mkimage -p <key>
+
+  -o  the image to write
+  -c  the kernel command line
+  -k  sign the image with the key
+  -v  the framebuffer mode that the bootloader sets, e.g. 1024x768x32
+  -p  print the public key of the key, which the bootloader embeds
+  Each module is named after its file. A key is a file of the 32-byte
+  Ed25519 seed, e.g. head -c 32 /dev/urandom > signing.key";
+
+// The bootloader reads the command line into a sector.
+const CMDLINE_MAX: usize = SECTOR_SIZE as usize;
+
+struct Image {
+    data: Vec<u8>,
+    header: Header,
+}
+
+impl Image {
+    // Pad the image up to the next sector.
+    fn pad(&mut self) {
+        let len =
+            (self.data.len() as u64 + SECTOR_SIZE - 1) & !(SECTOR_SIZE - 1);
+        self.data.resize(len as usize, 0);
+    }
+
+    fn append(
+        &mut self,
+        kind: u32,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<(), String> {
+        if name.len() >= NAME_MAX {
+            return Err(format!("{}: the name is too long", name));
+        }
+        let (sector, size) =
+            place(name, self.data.len() as u64, payload.len() as u64)?;
+        let mut entry = Entry {
+            kind,
+            sector,
+            size,
+            _reserved: 0,
+            sha256: sha256::digest(payload),
+            name: [0; NAME_MAX],
+        };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        self.header
+            .push(entry)
+            .map_err(|_| format!("{}: too many entries", name))?;
+        self.data.extend_from_slice(payload);
+        self.pad();
+        Ok(())
+    }
+}
+
+// The sector and the size of a payload of `len` bytes at `offset`, as the
+// entries address the image in 32 bit sectors and bytes.
+fn place(name: &str, offset: u64, len: u64) -> Result<(u32, u32), String> {
+    let sector = u32::try_from(offset / SECTOR_SIZE)
+        .map_err(|_| format!("{}: the image is too large", name))?;
+    let size = u32::try_from(len)
+        .map_err(|_| format!("{}: the file is too large", name))?;
+    Ok((sector, size))
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn read_key(path: &str) -> Result<[u8; ed25519::SEED_SIZE], String> {
+    let key = read(path)?;
+    let mut seed = [0; ed25519::SEED_SIZE];
+    if key.len() != seed.len() {
+        return Err(format!("{}: not a {}-byte seed", path, seed.len()));
+    }
+    seed.copy_from_slice(&key);
+    Ok(seed)
+}
+
+fn parse_video(s: &str) -> Result<VideoMode, String> {
+    let bad = || format!("{}: not a <width>x<height>x<bpp> mode", s);
+    let mut dims = s.split('x').map(|n| n.parse::<u16>());
+    match (dims.next(), dims.next(), dims.next(), dims.next()) {
+        (Some(Ok(width)), Some(Ok(height)), Some(Ok(bpp)), None)
+            if width > 0 && height > 0 && bpp > 0 && bpp <= 32 =>
+        {
+            Ok(VideoMode {
+                width,
+                height,
+                bpp: bpp as u8,
+                _reserved: [0; 3],
+            })
+        }
+        _ => Err(bad()),
+    }
+}
+
+fn build(
+    boot: &str,
+    kern: &str,
+    cmdline: &str,
+    mods: &[String],
+    key: Option<&str>,
+    video: Option<VideoMode>,
+) -> Result<Vec<u8>, String> {
+    if cmdline.len() >= CMDLINE_MAX {
+        return Err("the command line is too long".into());
+    }
+    let mut image = Image {
+        data: read(boot)?,
+        header: Header::new(),
+    };
+    // The header takes the sectors right after the bootloader.
+    image.pad();
+    let at = image.data.len();
+    image.data.resize(at + size_of::<Header>(), 0);
+    image.pad();
+
+    image.append(ENTRY_KERNEL, "kernel", &read(kern)?)?;
+    if !cmdline.is_empty() {
+        image.append(ENTRY_CMDLINE, "cmdline", cmdline.as_bytes())?;
+    }
+    for path in mods {
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        image.append(ENTRY_MODULE, name, &read(path)?)?;
+    }
+
+    if let Some(video) = video {
+        image.header.video = video;
+    }
+    if let Some(key) = key {
+        image.header.flags |= IMAGE_SIGNED;
+        image.header.signature =
+            ed25519::sign(&read_key(key)?, image.header.message().as_bytes());
+    }
+    image.header.seal();
+    image.data[at..at + size_of::<Header>()]
+        .copy_from_slice(image.header.as_bytes());
+    Ok(image.data)
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1)
+}
+
+fn fail(e: String) -> ! {
+    eprintln!("mkimage: {}", e);
+    process::exit(1)
+}
+
+fn main() {
+    let mut output = None;
+    let mut cmdline = String::new();
+    let mut key = None;
+    let mut video = None;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = args.next(),
+            "-c" => cmdline = args.next().unwrap_or_default(),
+            "-k" => key = args.next(),
+            "-v" => {
+                let mode = args.next().unwrap_or_else(|| usage());
+                video = Some(parse_video(&mode).unwrap_or_else(|e| fail(e)));
+            }
+            "-p" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                let seed = read_key(&path).unwrap_or_else(|e| fail(e));
+                for byte in ed25519::public_key(&seed).iter() {
+                    print!("{:02x}", byte);
+                }
+                println!();
+                return;
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => files.push(arg),
+        }
+    }
+    let output = match output {
+        Some(output) if files.len() >= 2 => output,
+        _ => usage(),
+    };
+
+    let image = build(
+        &files[0],
+        &files[1],
+        &cmdline,
+        &files[2..],
+        key.as_deref(),
+        video,
+    )
+    .unwrap_or_else(|e| fail(e));
+    fs::write(&output, image)
+        .unwrap_or_else(|e| fail(format!("{}: {}", output, e)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_at_the_32_bit_limits() {
+        let last = u32::MAX as u64 * SECTOR_SIZE;
+        assert_eq!(place("m", last, u32::MAX as u64), Ok((u32::MAX, u32::MAX)));
+        assert!(place("m", last + SECTOR_SIZE, 1).is_err());
+        assert!(place("m", 0, u32::MAX as u64 + 1).is_err());
+    }
+
+    #[test]
+    fn append_and_find() {
+        let mut image = Image {
+            data: vec![0; 3 * SECTOR_SIZE as usize],
+            header: Header::new(),
+        };
+        let kernel = vec![0x90; 0x201];
+        image.append(ENTRY_KERNEL, "kernel", &kernel).unwrap();
+        image.append(ENTRY_CMDLINE, "cmdline", b"quiet").unwrap();
+        assert!(image
+            .append(ENTRY_MODULE, &"m".repeat(NAME_MAX), b"")
+            .is_err());
+        image.header.seal();
+        assert!(image.header.is_valid());
+
+        let entry = image.header.find(ENTRY_KERNEL).unwrap();
+        assert_eq!((entry.sector, entry.size), (3, 0x201));
+        assert_eq!(entry.name(), "kernel");
+        assert_eq!(entry.sha256, sha256::digest(&kernel));
+        let entry = image.header.find(ENTRY_CMDLINE).unwrap();
+        assert_eq!(entry.sector, 5);
+        let at = entry.offset() as usize;
+        assert_eq!(&image.data[at..at + 5], b"quiet");
+        assert_eq!(image.data.len(), 6 * SECTOR_SIZE as usize);
+
+        for i in 2..ENTRIES_MAX {
+            image.append(ENTRY_MODULE, &i.to_string(), b"").unwrap();
+        }
+        assert!(image.append(ENTRY_MODULE, "extra", b"").is_err());
+    }
+}
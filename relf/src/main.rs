@@ -0,0 +1,349 @@
+//! Dumps ELF files as the elf crate sees them, like readelf.
+
+use elf::fmt::*;
+use elf::symbol::SymbolTable;
+use elf::ELF;
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::mem::size_of;
+use std::process;
+
+// The bootloader stages the front of the kernel image, up to the end of the
+// program header table, from KERN_ELF_BASE up to KERN_ELF_END.
+const BOOT_PREFIX_MAX: u64 = 0x80000 - 0x20000;
+
+const USAGE: &str = "usage: relf [-h] [-l] [-S] [-s] [-n] [-d] [-a] [--boot] \
+                     <file>
+
+  -h      the ELF header
+  -l      the program headers
+  -S      the section headers
+  -s      the symbol tables
+  -n      the notes
+  -d      the dynamic segment
+  -a      all of the above
+  --boot  parse the front of the image up to the end of the program
+          headers only, as the bootloader does";
+
+#[derive(Default)]
+struct Options {
+    header: bool,
+    phdrs: bool,
+    shdrs: bool,
+    symbols: bool,
+    notes: bool,
+    dynamic: bool,
+    boot: bool,
+}
+
+// The length of the front of `data` that the bootloader reads: the ELF
+// header and the program header table, wherever it lies. None if the
+// bootloader would refuse the image for the table being out of its reach.
+fn boot_prefix(data: &[u8]) -> Option<usize> {
+    let hdr_size = size_of::<ELFHeader64>();
+    if data.len() < hdr_size {
+        return Some(hdr_size);
+    }
+    let field = |off: usize, len: usize| {
+        data[off..off + len]
+            .iter()
+            .rev()
+            .fold(0u64, |v, &b| v << 8 | b as u64)
+    };
+    // e_phoff, e_phentsize and e_phnum of the 64 bit header.
+    let (phoff, phentsize, phnum) =
+        (field(0x20, 8), field(0x36, 2), field(0x38, 2));
+    match phoff.checked_add(phnum * phentsize) {
+        Some(end) if end <= BOOT_PREFIX_MAX => {
+            Some(end.max(hdr_size as u64) as usize)
+        }
+        _ => None,
+    }
+}
+
+// The name of a known value, or the raw value.
+fn name<T: Debug>(v: Option<T>, raw: u64) -> String {
+    match v {
+        Some(v) => format!("{:?}", v),
+        None => format!("0x{:x}", raw),
+    }
+}
+
+fn pflags(flags: PFlags) -> String {
+    [(PFlags::R, 'R'), (PFlags::W, 'W'), (PFlags::X, 'E')]
+        .iter()
+        .map(|&(f, c)| if flags.contains(f) { c } else { ' ' })
+        .collect()
+}
+
+fn shflags(flags: SHFlags64) -> String {
+    [
+        (SHFlags64::WRITE, 'W'),
+        (SHFlags64::ALLOC, 'A'),
+        (SHFlags64::EXECINSTR, 'X'),
+        (SHFlags64::MERGE, 'M'),
+        (SHFlags64::STRINGS, 'S'),
+        (SHFlags64::INFO_LINK, 'I'),
+        (SHFlags64::LINK_ORDER, 'L'),
+        (SHFlags64::GROUP, 'G'),
+        (SHFlags64::TLS, 'T'),
+    ]
+    .iter()
+    .filter(|&&(f, _)| flags.contains(f))
+    .map(|&(_, c)| c)
+    .collect()
+}
+
+fn dump_header(elf: &ELF) {
+    let hdr = elf.header();
+    println!("ELF Header:");
+    println!("  Class:                {:?}", elf.class());
+    println!(
+        "  OS/ABI:               {}",
+        name(hdr.osabi(), hdr.ei_osabi as u64)
+    );
+    println!(
+        "  Type:                 {}",
+        name(hdr.kind(), hdr.e_type as u64)
+    );
+    println!(
+        "  Machine:              {}",
+        name(hdr.machine(), hdr.e_machine as u64)
+    );
+    println!("  Entry point:          0x{:x}", hdr.e_entry);
+    println!(
+        "  Program headers:      {} at 0x{:x}",
+        hdr.e_phnum, hdr.e_phoff
+    );
+    println!(
+        "  Section headers:      {} at 0x{:x}",
+        hdr.e_shnum, hdr.e_shoff
+    );
+    println!("  Section name index:   {}", hdr.e_shstrndx);
+    if let Some(id) = elf.build_id() {
+        println!("  Build ID:             {}", id);
+    }
+    println!();
+}
+
+fn dump_phdrs(elf: &ELF) {
+    println!("Program Headers:");
+    println!(
+        "  {:<10} {:>10} {:>18} {:>18} {:>10} {:>10} {:<3} {:>6}",
+        "Type",
+        "Offset",
+        "VirtAddr",
+        "PhysAddr",
+        "FileSiz",
+        "MemSiz",
+        "Flg",
+        "Align"
+    );
+    for phdr in elf.phdrs() {
+        println!(
+            "  {:<10} {:>#10x} {:>#18x} {:>#18x} {:>#10x} {:>#10x} {:<3} {:>#6x}",
+            name(phdr.kind(), phdr.p_type as u64),
+            phdr.p_offset,
+            phdr.p_vaddr,
+            phdr.p_paddr,
+            phdr.p_filesz,
+            phdr.p_memsz,
+            pflags(phdr.flags()),
+            phdr.p_align
+        );
+    }
+    if let Some(tls) = elf.tls() {
+        println!(
+            "  TLS template: 0x{:x} bytes ({} initialized), aligned to 0x{:x}",
+            tls.size,
+            tls.image.len(),
+            tls.align
+        );
+    }
+    println!();
+}
+
+fn dump_shdrs(elf: &ELF) {
+    if elf.shdrs().next().is_none() {
+        println!("There are no section headers.\n");
+        return;
+    }
+    println!("Section Headers:");
+    println!(
+        "  [{:>2}] {:<20} {:<12} {:>18} {:>8} {:>8} {:>4} {:<4} {:>3} {:>4} {:>5}",
+        "Nr", "Name", "Type", "Address", "Off", "Size", "ES", "Flg", "Lk",
+        "Inf", "Al"
+    );
+    for (i, shdr) in elf.shdrs().enumerate() {
+        println!(
+            "  [{:>2}] {:<20} {:<12} {:>#18x} {:>8x} {:>8x} {:>4x} {:<4} {:>3} {:>4} {:>5}",
+            i,
+            elf.section_name(&shdr).unwrap_or("?"),
+            name(shdr.kind(), shdr.sh_type as u64),
+            shdr.sh_addr,
+            shdr.sh_offset,
+            shdr.sh_size,
+            shdr.sh_entsize,
+            shflags(shdr.flags()),
+            shdr.sh_link,
+            shdr.sh_info,
+            shdr.sh_addralign
+        );
+    }
+    println!();
+}
+
+fn dump_symbol_table(title: &str, table: SymbolTable) {
+    println!("Symbol table '{}' contains {} entries:", title, table.len());
+    println!(
+        "  {:>6} {:>18} {:>6} {:<8} {:<8} {:>5} Name",
+        "Num", "Value", "Size", "Type", "Bind", "Ndx"
+    );
+    for (i, sym) in table.iter().enumerate() {
+        println!(
+            "  {:>6} {:>#18x} {:>6} {:<8} {:<8} {:>5} {}",
+            i,
+            sym.st_value,
+            sym.st_size,
+            name(sym.kind(), (sym.st_info & 0xf) as u64),
+            name(sym.bind(), (sym.st_info >> 4) as u64),
+            sym.st_shndx,
+            table.name(&sym).unwrap_or("?")
+        );
+    }
+    println!();
+}
+
+fn dump_symbols(elf: &ELF) {
+    match (elf.symtab(), elf.dynsym()) {
+        (None, None) => println!("There are no symbol tables.\n"),
+        (symtab, dynsym) => {
+            if let Some(dynsym) = dynsym {
+                dump_symbol_table(".dynsym", dynsym);
+            }
+            if let Some(symtab) = symtab {
+                dump_symbol_table(".symtab", symtab);
+            }
+        }
+    }
+}
+
+fn dump_notes(elf: &ELF) {
+    for phdr in elf.phdrs() {
+        let notes = match elf.segment_notes(&phdr) {
+            Some(notes) => notes,
+            None => continue,
+        };
+        println!(
+            "Notes at offset 0x{:x} of size 0x{:x}:",
+            phdr.p_offset, phdr.p_filesz
+        );
+        println!("  {:<20} {:>10} Type", "Owner", "Size");
+        for note in notes {
+            println!(
+                "  {:<20} {:>#10x} 0x{:x}",
+                String::from_utf8_lossy(note.name),
+                note.desc.len(),
+                note.ntype
+            );
+            if note.is_gnu_build_id() {
+                println!("    Build ID: {}", elf::note::BuildId(note.desc));
+            }
+        }
+        println!();
+    }
+}
+
+fn dump_dynamic(elf: &ELF) {
+    let dynamic = match elf.dynamic() {
+        Some(dynamic) => dynamic,
+        None => {
+            println!("There is no dynamic segment.\n");
+            return;
+        }
+    };
+    println!("Dynamic segment:");
+    println!("  {:<20} Value", "Tag");
+    for dyn_ in dynamic.iter() {
+        println!(
+            "  {:<20} 0x{:x}",
+            name(dyn_.tag(), dyn_.d_tag as u64),
+            dyn_.d_val
+        );
+    }
+    for needed in dynamic.needed() {
+        println!("  Needed: {}", needed);
+    }
+    println!();
+}
+
+fn main() {
+    let mut opts = Options::default();
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-h" => opts.header = true,
+            "-l" => opts.phdrs = true,
+            "-S" => opts.shdrs = true,
+            "-s" => opts.symbols = true,
+            "-n" => opts.notes = true,
+            "-d" => opts.dynamic = true,
+            "-a" => {
+                opts.header = true;
+                opts.phdrs = true;
+                opts.shdrs = true;
+                opts.symbols = true;
+                opts.notes = true;
+                opts.dynamic = true;
+            }
+            "--boot" => opts.boot = true,
+            _ if arg.starts_with('-') || path.is_some() => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    });
+
+    let data = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("relf: {}: {}", path, e);
+        process::exit(1);
+    });
+    let parsed = if opts.boot {
+        let len = boot_prefix(&data).unwrap_or_else(|| {
+            eprintln!("relf: {}: program headers too large to boot", path);
+            process::exit(1);
+        });
+        ELF::parse_segments(&data[..data.len().min(len)])
+    } else {
+        ELF::parse(&data)
+    };
+    let elf = parsed.unwrap_or_else(|e| {
+        eprintln!("relf: {}: {}", path, e);
+        process::exit(1);
+    });
+
+    if opts.header {
+        dump_header(&elf);
+    }
+    if opts.phdrs {
+        dump_phdrs(&elf);
+    }
+    if opts.shdrs {
+        dump_shdrs(&elf);
+    }
+    if opts.symbols {
+        dump_symbols(&elf);
+    }
+    if opts.notes {
+        dump_notes(&elf);
+    }
+    if opts.dynamic {
+        dump_dynamic(&elf);
+    }
+}